@@ -1,5 +1,5 @@
 use core::time;
-use std::{fs::File, io::Write, thread};
+use std::{fs::File, io::{self, Read, Seek, SeekFrom, Write}, os::fd::AsRawFd, thread};
 
 pub fn set_gpio(gpio: &str) {
   println!("{}", gpio);
@@ -9,7 +9,7 @@ pub fn set_gpio(gpio: &str) {
                             .open("/sys/class/gpio/export")
                             .unwrap();
     
-  file.write(gpio.as_bytes()).expect("Write failed");
+  file.write_all(gpio.as_bytes()).expect("Write failed");
   file.flush().expect("Flush failed");
   thread::sleep(time::Duration::from_millis(10));
 }
@@ -22,7 +22,7 @@ pub fn set_output(gpio: &str) {
                             .truncate(true)
                             .open(filepath)
                             .unwrap();
-  file.write(b"out").expect("Write failed");
+  file.write_all(b"out").expect("Write failed");
   thread::sleep(time::Duration::from_millis(10));
 }
 
@@ -33,7 +33,7 @@ pub fn set_input(gpio: &str) {
                             .truncate(true)
                             .open(filepath)
                             .unwrap();
-  file.write(b"in").expect("Write failed");
+  file.write_all(b"in").expect("Write failed");
   thread::sleep(time::Duration::from_millis(10));
 }
 
@@ -44,8 +44,7 @@ pub fn set_high(gpio: &str) {
                             .truncate(true)
                             .open(filepath)
                             .unwrap();
-  file.write(b"1").expect("Write failed");
-  thread::sleep(time::Duration::from_millis(10));
+  file.write_all(b"1").expect("Write failed");
 }
 
 pub fn set_low(gpio: &str) {
@@ -55,6 +54,61 @@ pub fn set_low(gpio: &str) {
                             .truncate(true)
                             .open(filepath)
                             .unwrap();
-  file.write(b"0").expect("Write failed");
-  thread::sleep(time::Duration::from_millis(10));
-}
\ No newline at end of file
+  file.write_all(b"0").expect("Write failed");
+}
+
+pub fn is_high(gpio: &str) -> bool {
+  let filepath = format!("/sys/class/gpio/gpio{}/value", gpio);
+  let mut value = [0u8];
+  File::open(filepath)
+    .unwrap()
+    .read_exact(&mut value)
+    .expect("Read failed");
+  value[0] == b'1'
+}
+
+// Which transitions make the value file signal poll(), one of "none", "rising", "falling" or "both"
+pub fn set_edge(gpio: &str, edge: &str) {
+  let filepath = format!("/sys/class/gpio/gpio{}/edge", gpio);
+  let mut file: File = std::fs::OpenOptions::new()
+                            .write(true)
+                            .truncate(true)
+                            .open(filepath)
+                            .unwrap();
+  file.write_all(edge.as_bytes()).expect("Write failed");
+}
+
+// Blocks until the input reads high, sleeping in the kernel until the next edge instead of polling
+// the value file. Needs set_edge(gpio, "rising"). Returns false if timeout ran out first; None
+// waits forever.
+pub fn wait_high(gpio: &str, timeout: Option<time::Duration>) -> bool {
+  let filepath = format!("/sys/class/gpio/gpio{}/value", gpio);
+  let mut file = File::open(filepath).unwrap();
+  let timeout_ms = match timeout {
+    Some(timeout) => timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32,
+    None => -1,
+  };
+
+  let mut value = [0u8];
+  let mut waited = false;
+  loop {
+    // reading the value also acknowledges any edge that is already pending
+    file.seek(SeekFrom::Start(0)).expect("Seek failed");
+    file.read_exact(&mut value).expect("Read failed");
+    if value[0] == b'1' {
+      return true;
+    }
+    if waited {
+      return false;
+    }
+
+    // sysfs reports an edge as an exceptional condition on the value file
+    let mut fds = libc::pollfd { fd: file.as_raw_fd(), events: libc::POLLPRI | libc::POLLERR, revents: 0 };
+    let ret = unsafe { libc::poll(&mut fds, 1, timeout_ms) };
+    if ret < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+      panic!("Poll failed: {}", io::Error::last_os_error());
+    }
+    // a timeout gets one last look at the value; an edge or a signal goes round again
+    waited = ret == 0;
+  }
+}
@@ -3,12 +3,28 @@
 
 extern crate spidev;
 
-use std::io;
-// use std::io::prelude::*;
-use spidev::{Spidev, SpidevOptions, SpidevTransfer, SpiModeFlags};
+use std::{env, io, process};
+use spidev::{Spidev, SpidevOptions, SpiModeFlags};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::Duration;
-pub mod gpio;
+use tel_sw::gpio;
+use tel_sw::sx1280::{Bandwidth, CodingRate, LoRaConfig, Register, SpreadingFactor, SX1280};
+
+const RX_3V3: &str = "49";
+const HF_NRESET: &str = "77";
+const HF_CS: &str = "81";
+const LF_CS: &str = "86";
+const GPS_CS: &str = "44";
+// HF-BUSY has no confirmed GPIO number on the TEL board yet, so it's taken from the environment
+const HF_BUSY_VAR: &str = "TEL_HF_BUSY";
+
+// Set from the signal handler once Ctrl-C or a kill asks an RF test to end
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_stop(_signal: libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
 
 fn create_spi() -> io::Result<Spidev> {
     let mut spi = Spidev::open("/dev/spidev0.0")?;
@@ -21,130 +37,92 @@ fn create_spi() -> io::Result<Spidev> {
     Ok(spi)
 }
 
-/// perform half duplex operations using Read and Write traits
-// fn half_duplex(spi: &mut Spidev) -> io::Result<()> {
-//     let mut rx_buf = [0_u8; 10];
-//     spi.write(&[0x01, 0x02, 0x03])?;
-//     spi.read(&mut rx_buf)?;
-//     println!("{:?}", rx_buf);
-//     Ok(())
-// }
-
-/// Perform full duplex operations using Ioctl
-fn full_duplex(spi: &mut Spidev, tx_buf: &[u8], rx_buf: &mut [u8]) -> io::Result<()> {
-    // "write" transfers are also reads at the same time with
-    // the read having the same length as the write
-    {
-        let mut transfer = SpidevTransfer::read_write(&tx_buf, rx_buf);
-        spi.transfer(&mut transfer)?;
+// GPIO number of HF-BUSY, e.g. TEL_HF_BUSY=76
+fn hf_busy() -> &'static str {
+    match env::var(HF_BUSY_VAR) {
+        Ok(pin) if !pin.is_empty() && pin.bytes().all(|b| b.is_ascii_digit()) => Box::leak(pin.into_boxed_str()),
+        _ => {
+            eprintln!("set {} to the GPIO number of HF-BUSY", HF_BUSY_VAR);
+            process::exit(2);
+        }
     }
-    println!("{:?}", rx_buf);
-    Ok(())
 }
 
 fn main() {
-    let mut spi = create_spi().unwrap();
-    
-    gpio::set_output("49");
-    gpio::set_high("49"); // 3V3-RX
-
-    gpio::set_output("77");
-    gpio::set_low("77"); // HF-NRESET
-    thread::sleep(Duration::from_millis(100));
-    gpio::set_high("77");
-
-    gpio::set_output("81"); // HF-CS
-    gpio::set_high("81");
-    gpio::set_output("44"); // GPS-CS
-    gpio::set_high("44");
-    gpio::set_output("86"); // LF-CS
-
-    let mut tx_buf = [0x1D, 0x08, 0xAC, 0x00, 0x00, 0x00];
-    let mut rx_buf = [0; 6];
-
-    gpio::set_low("86");
-    thread::sleep(Duration::from_micros(200));
-    println!("{:?}", full_duplex(&mut spi, &mut tx_buf, &mut rx_buf).unwrap());
-    gpio::set_high("86");
-    thread::sleep(Duration::from_millis(100));
-
-    let mut tx_buf_wr = [0x0D, 0x08, 0xAC, 0x95];
-    let mut rx_buf_wr = [0; 4];
-    gpio::set_low("86");
-    thread::sleep(Duration::from_micros(200));
-    println!("{:?}", full_duplex(&mut spi, &mut tx_buf_wr, &mut rx_buf_wr).unwrap());
-    gpio::set_high("86");
-    thread::sleep(Duration::from_millis(100));
-    loop {
-        gpio::set_low("86");
-        thread::sleep(Duration::from_micros(200));
-        println!("{:?}", full_duplex(&mut spi, &mut tx_buf, &mut rx_buf).unwrap());
-        gpio::set_high("86");
+    let hf_busy = hf_busy();
+    let spi = create_spi().unwrap();
+
+    // keep the other devices on the bus deselected
+    gpio::set_output(LF_CS);
+    gpio::set_high(LF_CS);
+    gpio::set_output(GPS_CS);
+    gpio::set_high(GPS_CS);
+
+    let mut radio = SX1280::new(spi, HF_CS, hf_busy, HF_NRESET);
+    radio.set_power_pin(RX_3V3);
+    radio.init().unwrap();
+    radio.configure_lora(&LoRaConfig {
+        sf: SpreadingFactor::Sf7,
+        bw: Bandwidth::Khz812_5,
+        cr: CodingRate::Cr4_5 { long_interleave: false },
+        ..LoRaConfig::default()
+    }).unwrap();
+
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        None => monitor(&mut radio),
+        Some(mode @ ("cw" | "preamble")) if args.len() == 4 => {
+            let (Ok(freq), Ok(pwr)) = (args[2].parse(), args[3].parse()) else {
+                usage(&args[0]);
+            };
+            test_tx(&mut radio, mode, freq, pwr);
+        }
+        Some(_) => usage(&args[0]),
+    }
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!("usage: {} [cw|preamble <frequency MHz> <power dBm>]", program);
+    process::exit(2);
+}
+
+// Keys up a carrier or an endless preamble for the spectrum analyzer until Ctrl-C. The radio goes
+// back to standby when the driver's guard is dropped, which happens on the way out of here both
+// after the signal and while unwinding from a panic.
+fn test_tx(radio: &mut SX1280, mode: &str, freq: f32, pwr: i8) {
+    let handler = request_stop as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+    }
+
+    let _test = match mode {
+        "cw" => radio.continuous_wave(freq, pwr),
+        _ => radio.continuous_preamble(freq, pwr),
+    }.unwrap();
+    println!("{} at {} MHz, {} dBm, Ctrl-C to stop", mode, freq, pwr);
+
+    while !STOP.load(Ordering::SeqCst) {
         thread::sleep(Duration::from_millis(100));
     }
+    println!("back to standby");
 }
 
-    // HF
-    // gpio::set_output("49");
-    // gpio::set_high("49"); // 3V3-RX
-
-    // gpio::set_output("9");
-    // gpio::set_low("9"); // HF-NRESET
-    // thread::sleep(Duration::from_millis(100));
-    // gpio::set_high("9");
-
-    // gpio::set_output("86"); // LF-CS
-    // gpio::set_high("86");
-    // gpio::set_output("44"); // GPS-CS
-    // gpio::set_high("44");
-    // gpio::set_output("81"); // HF-CS
-
-    // let mut tx_buf = [0x19, 0x08, 0x91, 0x00, 0x00, 0x00];
-    // let mut rx_buf = [0; 6];
-
-    // gpio::set_low("81");
-    // thread::sleep(Duration::from_micros(200));
-    // println!("{:?}", full_duplex(&mut spi, &mut tx_buf, &mut rx_buf).unwrap());
-    // gpio::set_high("81");
-    // thread::sleep(Duration::from_millis(100));
-
-    // let mut tx_buf_wr = [0x18, 0x08, 0x91, 0x35];
-    // let mut rx_buf_wr = [0; 4];
-    // gpio::set_low("81");
-    // thread::sleep(Duration::from_micros(200));
-    // println!("{:?}", full_duplex(&mut spi, &mut tx_buf_wr, &mut rx_buf_wr).unwrap());
-    // gpio::set_high("81");
-    // thread::sleep(Duration::from_millis(100));
-    // loop {
-    //     gpio::set_low("81");
-    //     thread::sleep(Duration::from_micros(200));
-    //     println!("{:?}", full_duplex(&mut spi, &mut tx_buf, &mut rx_buf).unwrap());
-    //     gpio::set_high("81");
-    //     thread::sleep(Duration::from_millis(100));
-    // }
-
-    // GPS
-    // gpio::set_output("15");
-    // gpio::set_high("15"); // 3V3-GPS
-
-    // gpio::set_output("45");
-    // gpio::set_low("45"); // GPS-NRESET
-    // thread::sleep(Duration::from_millis(100));
-    // gpio::set_high("45");
-
-    // gpio::set_output("86"); // LF-CS
-    // gpio::set_high("86");
-    // gpio::set_output("81"); // HF-CS
-    // gpio::set_high("81");
-    // gpio::set_output("44"); // GPS-CS
-    
-    // let mut tx_buf = [0xFD, 0xFE];
-    // let mut rx_buf = [0; 2];
-
-    // loop {
-    //     gpio::set_low("44");
-    //     thread::sleep(Duration::from_micros(200));
-    //     println!("{:?}", full_duplex(&mut spi, &mut tx_buf, &mut rx_buf).unwrap());
-    //     gpio::set_high("44");
-    //     thread::sleep(Duration::from_millis(100));
-    // }
\ No newline at end of file
+// Watches the configuration registers and puts back any that drift
+fn monitor(radio: &mut SX1280) {
+    println!("{}", radio.get_status().unwrap());
+    println!("{:#04x}", radio.read_register_value(Register::RxGain).unwrap());
+    radio.write_register_value(Register::RxGain, 0x35).unwrap();
+
+    loop {
+        match radio.verify() {
+            Ok(drift) if drift.is_empty() => (),
+            Ok(drift) => {
+                println!("{:?}", drift);
+                radio.restore_registers(&drift).unwrap();
+            }
+            Err(err) => println!("{}", err),
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,261 @@
+use std::time::Duration;
+
+use super::ble::BleConfig;
+use super::error::Result;
+use super::flrc::{FlrcCodingRate, FlrcConfig, PacketLength, SyncWordMatch};
+use super::gfsk::GfskConfig;
+use super::lora::{decode_preamble, encode_preamble, LoRaConfig, LoRaHeader, SpreadingFactor};
+
+// FLRC sends this preamble after the configurable AGC preamble
+const FLRC_PREAMBLE_BITS: u32 = 21;
+
+// Time on air of a LoRa packet - SX1280 datasheet v3.0 section 7.4.4. The preamble is rounded up
+// to the length SetPacketParams actually sends.
+pub fn lora_time_on_air(config: &LoRaConfig, payload_length: u8) -> Result<Duration> {
+    let sf = config.sf.value() as i32;
+    let preamble = decode_preamble(encode_preamble(config.preamble)?) as f64;
+
+    // SF5 and SF6 use a longer sync sequence
+    let sync = match config.sf {
+        SpreadingFactor::Sf5 | SpreadingFactor::Sf6 => 6.25,
+        _ => 4.25,
+    };
+    let data_symbols = if config.cr.is_long_interleaved() {
+        long_interleaved_symbols(config, payload_length)
+    } else {
+        block_symbols(config, payload_length)
+    };
+
+    let symbol = 2f64.powi(sf) / (config.bw.khz() as f64 * 1000.0);
+    let symbols = preamble + sync + data_symbols as f64;
+    Ok(Duration::from_secs_f64(symbols * symbol))
+}
+
+// Bits of payload and CRC
+fn lora_payload_bits(config: &LoRaConfig, payload_length: u8) -> u32 {
+    8 * payload_length as u32 + if config.crc { 16 } else { 0 }
+}
+
+// Bits a symbol carries after the header block. SF11 and SF12 always run in low data rate mode,
+// carrying two bits less per symbol.
+fn lora_bits_per_symbol(sf: SpreadingFactor) -> u32 {
+    match sf {
+        SpreadingFactor::Sf11 | SpreadingFactor::Sf12 => sf.value() as u32 - 2,
+        _ => sf.value() as u32,
+    }
+}
+
+// Symbols after the sync word with the 4/5 to 4/8 block codes - section 7.4.4.1: an 8 symbol header
+// block, then blocks of 4 * SF bits coded into 4 + CR symbols. SF5 and SF6 drop the 8 extra bits
+// of the header block.
+fn block_symbols(config: &LoRaConfig, payload_length: u8) -> u32 {
+    let sf = config.sf.value() as i32;
+    let header = match config.header {
+        LoRaHeader::Explicit => 20,
+        LoRaHeader::Implicit(_) => 0,
+    };
+    let extra = match config.sf {
+        SpreadingFactor::Sf5 | SpreadingFactor::Sf6 => 0,
+        _ => 8,
+    };
+
+    let bits = (lora_payload_bits(config, payload_length) as i32 - 4 * sf + extra + header).max(0) as u32;
+    let blocks = bits.div_ceil(4 * lora_bits_per_symbol(config.sf));
+    8 + blocks * config.cr.denominator() as u32
+}
+
+// Symbols after the sync word with long interleaving - section 7.4.4.2. What is left after the
+// header block is coded as a whole rather than in blocks.
+fn long_interleaved_symbols(config: &LoRaConfig, payload_length: u8) -> u32 {
+    let sf = config.sf.value() as u32;
+    let fine_sync = matches!(config.sf, SpreadingFactor::Sf5 | SpreadingFactor::Sf6);
+    let bits = lora_payload_bits(config, payload_length);
+    let denominator = config.cr.denominator() as u32;
+    let bits_per_symbol = lora_bits_per_symbol(config.sf);
+
+    match config.header {
+        LoRaHeader::Explicit => {
+            // whole bytes of payload that fit next to the header in the first 8 symbols
+            let header_space = ((4 * sf + if fine_sync { 8 } else { 0 }).saturating_sub(28)) & !0x07;
+            if bits <= header_space {
+                8
+            } else {
+                8 + ((bits - header_space) * denominator).div_ceil(4 * bits_per_symbol)
+            }
+        }
+        LoRaHeader::Implicit(_) => {
+            // the first 8 symbols are always sent and carry SF - 2 coded bits each, or SF at SF5
+            // and SF6. Both sides are scaled by 4, the numerator of the code rate.
+            let start_bits = if fine_sync { sf } else { sf - 2 };
+            let first_block = 4 * 8 * start_bits;
+            let coded = bits * denominator;
+            if coded <= first_block {
+                8
+            } else {
+                8 + (coded - first_block).div_ceil(4 * bits_per_symbol)
+            }
+        }
+    }
+}
+
+// Time on air of an FLRC packet - SX1280 datasheet v3.0 section 7.3.4. The AGC preamble, the
+// fixed 21-bit preamble and the sync word go out uncoded; header, payload and CRC are coded, and
+// the convolutional codes add a 6-bit tail.
+pub fn flrc_time_on_air(config: &FlrcConfig, payload_length: u8) -> Duration {
+    let sync = if config.sync_match == SyncWordMatch::Off { 0 } else { 32 };
+    let header = match config.length {
+        PacketLength::Variable => 16,
+        PacketLength::Fixed(_) => 0,
+    };
+    let tail = if config.cr == FlrcCodingRate::Cr1_0 { 0 } else { 6 };
+
+    let uncoded = config.preamble_bits as u32 + FLRC_PREAMBLE_BITS + sync;
+    let data = header + 8 * (payload_length as u32 + config.crc.bytes() as u32) + tail;
+    let coded = (data as f64 / config.cr.ratio() as f64).ceil();
+    Duration::from_secs_f64((uncoded as f64 + coded) / config.bit_rate.bits_per_second() as f64)
+}
+
+// Time on air of a GFSK packet: preamble, sync word, length byte if variable, payload and CRC
+pub fn gfsk_time_on_air(config: &GfskConfig, payload_length: u8) -> Duration {
+    let header = match config.length {
+        PacketLength::Variable => 8,
+        PacketLength::Fixed(_) => 0,
+    };
+    let bits = config.preamble_bits as u32
+        + 8 * config.sync_word_length as u32
+        + header
+        + 8 * (payload_length as u32 + config.crc.bytes() as u32);
+    Duration::from_secs_f64(bits as f64 / (config.bit_rate.kbps() as f64 * 1000.0))
+}
+
+// Time on air of a BLE packet at 1 Mbps: 1 byte preamble, 4 byte access address, the PDU
+// (header and payload) and the 3 byte CRC
+pub fn ble_time_on_air(config: &BleConfig, pdu_length: u8) -> Duration {
+    let crc = if config.crc { 3 } else { 0 };
+    let bytes = 1 + 4 + pdu_length as u64 + crc;
+    Duration::from_micros(8 * bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sx1280::{Bandwidth, CodingRate, FlrcBitRate, GfskBitRate, GfskCrc};
+
+    fn assert_micros(actual: Duration, expected_us: f64) {
+        let actual_us = actual.as_secs_f64() * 1e6;
+        assert!((actual_us - expected_us).abs() < 0.01, "{} us, expected {} us", actual_us, expected_us);
+    }
+
+    fn lora(sf: SpreadingFactor, bw: Bandwidth, cr: CodingRate) -> LoRaConfig {
+        LoRaConfig { sf, bw, cr, preamble: 12, header: LoRaHeader::Explicit, crc: true, invert_iq: false }
+    }
+
+    #[test]
+    fn lora_sf7() {
+        let config = lora(SpreadingFactor::Sf7, Bandwidth::Khz812_5, CodingRate::Cr4_5 { long_interleave: false });
+        // 80 + 16 - 28 + 8 + 20 = 96 bits -> 4 blocks of 5 symbols, 12 + 4.25 + 8 + 20 symbols of 128 / 812.5 kHz
+        assert_micros(lora_time_on_air(&config, 10).unwrap(), 44.25 * 128.0 / 0.8125);
+    }
+
+    #[test]
+    fn lora_sf5_has_longer_sync_and_no_extra_bits() {
+        let config = LoRaConfig {
+            preamble: 8,
+            header: LoRaHeader::Implicit(4),
+            crc: false,
+            ..lora(SpreadingFactor::Sf5, Bandwidth::Khz1625, CodingRate::Cr4_5 { long_interleave: false })
+        };
+        // 32 - 20 = 12 bits -> 1 block of 5 symbols, 8 + 6.25 + 8 + 5 symbols of 32 / 1625 kHz
+        assert_micros(lora_time_on_air(&config, 4).unwrap(), 27.25 * 32.0 / 1.625);
+    }
+
+    #[test]
+    fn lora_sf12_low_data_rate() {
+        let config = lora(SpreadingFactor::Sf12, Bandwidth::Khz203_125, CodingRate::Cr4_8 { long_interleave: false });
+        // 160 + 16 - 48 + 8 + 20 = 156 bits at 40 bits per block -> 4 blocks of 8 symbols
+        assert_micros(lora_time_on_air(&config, 20).unwrap(), 56.25 * 4096.0 / 0.203125);
+    }
+
+    #[test]
+    fn lora_empty_payload_still_sends_header_block() {
+        let config = LoRaConfig {
+            header: LoRaHeader::Implicit(0),
+            crc: false,
+            ..lora(SpreadingFactor::Sf12, Bandwidth::Khz203_125, CodingRate::Cr4_5 { long_interleave: false })
+        };
+        assert_micros(lora_time_on_air(&config, 0).unwrap(), 24.25 * 4096.0 / 0.203125);
+    }
+
+    // Expected values below are worked by hand from the section 7.4.4.2 formulas
+
+    #[test]
+    fn lora_long_interleaving() {
+        let config = lora(SpreadingFactor::Sf7, Bandwidth::Khz812_5, CodingRate::Cr4_8 { long_interleave: true });
+        // no header space at SF7: 8 + ceil(96 * 8 / 28) = 36 symbols, 52.25 in all
+        assert_micros(lora_time_on_air(&config, 10).unwrap(), 8231.385);
+    }
+
+    #[test]
+    fn lora_long_interleaving_header_space() {
+        let config = LoRaConfig {
+            crc: false,
+            ..lora(SpreadingFactor::Sf12, Bandwidth::Khz812_5, CodingRate::Cr4_8 { long_interleave: true })
+        };
+        // 16 bits of header space at SF12: 2 bytes fit the header block, 24.25 symbols
+        assert_micros(lora_time_on_air(&config, 2).unwrap(), 122249.846);
+        // 3 bytes spill over: 8 + ceil(8 * 8 / 40) = 10 symbols, 26.25 in all
+        assert_micros(lora_time_on_air(&config, 3).unwrap(), 132332.308);
+    }
+
+    #[test]
+    fn lora_long_interleaving_implicit_sends_whole_first_block() {
+        let config = LoRaConfig {
+            header: LoRaHeader::Implicit(4),
+            crc: false,
+            ..lora(SpreadingFactor::Sf9, Bandwidth::Khz812_5, CodingRate::Cr4_5 { long_interleave: true })
+        };
+        // a short packet still takes the fixed 8 symbols after the sync word: 12 + 4.25 + 8 symbols
+        // of 512 / 812.5 kHz
+        assert_micros(lora_time_on_air(&config, 4).unwrap(), 15281.231);
+
+        // the same minimum as the block codes
+        let single = LoRaConfig { header: LoRaHeader::Implicit(1), ..config };
+        let block = LoRaConfig { cr: CodingRate::Cr4_5 { long_interleave: false }, ..single };
+        assert_micros(lora_time_on_air(&single, 1).unwrap(), 15281.231);
+        assert_eq!(lora_time_on_air(&single, 1).unwrap(), lora_time_on_air(&block, 1).unwrap());
+    }
+
+    #[test]
+    fn lora_preamble_is_rounded_up() {
+        let short = lora(SpreadingFactor::Sf9, Bandwidth::Khz812_5, CodingRate::Cr4_7);
+        let rounded = LoRaConfig { preamble: 34, ..short };
+        let exact = LoRaConfig { preamble: 36, ..short };
+        assert_eq!(lora_time_on_air(&rounded, 16).unwrap(), lora_time_on_air(&exact, 16).unwrap());
+        assert!(lora_time_on_air(&LoRaConfig { preamble: 3, ..short }, 16).is_err());
+    }
+
+    #[test]
+    fn flrc() {
+        let config = FlrcConfig { bit_rate: FlrcBitRate::Kbps650, cr: FlrcCodingRate::Cr3_4, ..FlrcConfig::default() };
+        // 32 AGC preamble + 21 preamble + 32 sync uncoded, ceil((16 + 80 + 16 + 6) * 4 / 3) = 158
+        // coded bits, 243 bits at 650 kbps
+        assert_micros(flrc_time_on_air(&config, 10), 373.846);
+
+        // 85 uncoded bits and 96 bits of payload and CRC without header or tail
+        let uncoded = FlrcConfig { cr: FlrcCodingRate::Cr1_0, length: PacketLength::Fixed(10), ..config };
+        assert_micros(flrc_time_on_air(&uncoded, 10), 278.462);
+    }
+
+    #[test]
+    fn gfsk() {
+        let config = GfskConfig { bit_rate: GfskBitRate::Br800Bw2400, crc: GfskCrc::Bytes2, ..GfskConfig::default() };
+        // 32 preamble + 16 sync + 8 length + 80 payload + 16 CRC
+        assert_micros(gfsk_time_on_air(&config, 10), 152.0 / 0.8);
+    }
+
+    #[test]
+    fn ble() {
+        // 8 preamble + 32 access address + 176 PDU + 24 CRC at 1 Mbps
+        assert_micros(ble_time_on_air(&BleConfig::default(), 22), 240.0);
+    }
+}
@@ -0,0 +1,150 @@
+use super::error::{Result, Sx1280Error};
+
+// BLE-PHY always runs at 1 Mbps in 1.2 MHz with modulation index 0.5 and BT 0.5
+pub const BLE_MODULATION_PARAMS: [u8; 3] = [0x45, 0x01, 0x20];
+
+// Access address and CRC initial value every advertising channel PDU uses - Bluetooth Core v5.0
+// Vol 6 Part B section 2.1.2
+pub const BLE_ADVERTISING_ACCESS_ADDRESS: u32 = 0x8E89_BED6;
+pub const BLE_ADVERTISING_CRC_INIT: u32 = 0x55_5555;
+pub const BLE_ADVERTISING_CHANNELS: [u8; 3] = [37, 38, 39];
+pub const BLE_MAX_CHANNEL: u8 = 39;
+
+// SX1280_CMD_SET_PACKET_PARAMS, the largest PDU payload the receiver will accept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BleConnectionState {
+    Payload31,
+    Payload37,
+    Test,
+    Payload255,
+}
+
+impl BleConnectionState {
+    pub fn param(&self) -> u8 {
+        match *self {
+            BleConnectionState::Payload31 => 0x00,
+            BleConnectionState::Payload37 => 0x20,
+            BleConnectionState::Test => 0x40,
+            BleConnectionState::Payload255 => 0x80,
+        }
+    }
+
+    pub fn max_payload_length(&self) -> u8 {
+        match *self {
+            BleConnectionState::Payload31 => 31,
+            BleConnectionState::Payload37 | BleConnectionState::Test => 37,
+            BleConnectionState::Payload255 => 255,
+        }
+    }
+}
+
+// Payload sent in BleConnectionState::Test, for PHY conformance testing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BleTestPayload {
+    Prbs9,
+    Eyelong1_0,
+    Eyeshort1_0,
+    Prbs15,
+    All1,
+    All0,
+    Eyelong0_1,
+    Eyeshort0_1,
+}
+
+impl BleTestPayload {
+    pub fn param(&self) -> u8 {
+        match *self {
+            BleTestPayload::Prbs9 => 0x00,
+            BleTestPayload::Eyelong1_0 => 0x04,
+            BleTestPayload::Eyeshort1_0 => 0x08,
+            BleTestPayload::Prbs15 => 0x0C,
+            BleTestPayload::All1 => 0x10,
+            BleTestPayload::All0 => 0x14,
+            BleTestPayload::Eyelong0_1 => 0x18,
+            BleTestPayload::Eyeshort0_1 => 0x1C,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BleConfig {
+    pub connection_state: BleConnectionState,
+    pub crc: bool,
+    pub test_payload: BleTestPayload,
+    pub whitening: bool,
+    pub access_address: u32,
+    // 24-bit CRC initial value
+    pub crc_init: u32,
+    // channel index 0 to 39, which sets both the frequency and the whitening seed
+    pub channel: u8,
+}
+
+impl Default for BleConfig {
+    fn default() -> Self {
+        BleConfig {
+            connection_state: BleConnectionState::Payload37,
+            crc: true,
+            test_payload: BleTestPayload::Prbs9,
+            whitening: true,
+            access_address: BLE_ADVERTISING_ACCESS_ADDRESS,
+            crc_init: BLE_ADVERTISING_CRC_INIT,
+            channel: BLE_ADVERTISING_CHANNELS[0],
+        }
+    }
+}
+
+impl BleConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.channel > BLE_MAX_CHANNEL {
+            return Err(Sx1280Error::InvalidChannel(self.channel));
+        }
+        if self.crc_init > 0xFF_FFFF {
+            return Err(Sx1280Error::InvalidCrcConfiguration(3));
+        }
+        Ok(())
+    }
+
+    pub fn packet_params(&self) -> [u8; 7] {
+        [
+            self.connection_state.param(),
+            if self.crc { 0x10 } else { 0x00 },
+            self.test_payload.param(),
+            if self.whitening { 0x00 } else { 0x08 },
+            0x00,
+            0x00,
+            0x00,
+        ]
+    }
+
+    // Checks a raw PDU (2-byte header followed by the payload) against its own length field and
+    // the connection state's limit
+    pub fn validate_pdu(&self, pdu: &[u8]) -> Result<()> {
+        if pdu.len() < 2
+            || pdu.len() != pdu[1] as usize + 2
+            || pdu[1] > self.connection_state.max_payload_length()
+        {
+            return Err(Sx1280Error::InvalidPayloadLength(pdu.len()));
+        }
+        Ok(())
+    }
+}
+
+// Centre frequency in MHz of a BLE channel index. Advertising channels 37, 38 and 39 sit at
+// 2402, 2426 and 2480 MHz, in the gaps between the data channels.
+pub fn ble_channel_frequency(channel: u8) -> Result<f32> {
+    let mhz = match channel {
+        0..=10 => 2404 + 2 * channel as u32,
+        11..=36 => 2428 + 2 * (channel as u32 - 11),
+        37 => 2402,
+        38 => 2426,
+        39 => 2480,
+        _ => return Err(Sx1280Error::InvalidChannel(channel)),
+    };
+    Ok(mhz as f32)
+}
+
+// Whitening LFSR seed for a channel: bit 6 set, channel index in bits 5:0 - Bluetooth Core v5.0
+// Vol 6 Part B section 3.2
+pub fn ble_whitening_seed(channel: u8) -> u8 {
+    0x40 | (channel & 0x3F)
+}
@@ -1,5 +1,6 @@
 // Represents commands that can be sent to the SX1280
-enum Command {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
     GetStatus,
     WriteRegister,
     ReadRegister,
@@ -39,49 +40,65 @@ enum Command {
 }
 
 impl Command {
-    fn opcode(&self) -> u8 {
+    pub fn opcode(&self) -> u8 {
         match *self {
-            GetStatus => 0xc0,
-            WriteRegister => 0x18,
-            ReadRegister => 0x19,
-            WriteBuffer => 0x1A,
-            ReadBuffer => 0x1B,
-            SetSleep => 0x84,
-            SetStandby => 0x80,
-            SetFs => 0xC1,
-            SetTx => 0x83,
-            SetRx => 0x82,
-            SetRxDutyCycle => 0x94,
-            SetCAD => 0xC5,
-            SetTxContinuousWave => 0xD1,
-            SetTxContinuousPreamble => 0xD2,
-            SetPacketType => 0x8A,
-            GetPacketType => 0x03,
-            SetRfFrequency => 0x86,
-            SetTxParams => 0x8E,
-            SetCadParams => 0x88,
-            SetBufferBaseAddress => 0x8F,
-            SetModulationParams => 0x8B,
-            SetPacketParams => 0x8C,
-            GetRxBufferStatus => 0x17,
-            GetPacketStatus => 0x1D,
-            GetRssilnst => 0x1F,
-            SetDioIrqParams => 0x8D,
-            GetIrqStatus => 0x15,
-            ClearIrqStatus => 0x97,
-            SetRegulatorMode => 0x96,
-            SetSaveContext => 0xD5,
-            SetAutoFS => 0x9E,
-            SetAutoTx => 0x98,
-            SetLongPreamble => 0x9B,
-            SetUartSpeed => 0x9D,
-            SetRangingRole => 0xA3,
-            SetAdvancedRanging => 0x9A,
+            Command::GetStatus => 0xC0,
+            Command::WriteRegister => 0x18,
+            Command::ReadRegister => 0x19,
+            Command::WriteBuffer => 0x1A,
+            Command::ReadBuffer => 0x1B,
+            Command::SetSleep => 0x84,
+            Command::SetStandby => 0x80,
+            Command::SetFs => 0xC1,
+            Command::SetTx => 0x83,
+            Command::SetRx => 0x82,
+            Command::SetRxDutyCycle => 0x94,
+            Command::SetCAD => 0xC5,
+            Command::SetTxContinuousWave => 0xD1,
+            Command::SetTxContinuousPreamble => 0xD2,
+            Command::SetPacketType => 0x8A,
+            Command::GetPacketType => 0x03,
+            Command::SetRfFrequency => 0x86,
+            Command::SetTxParams => 0x8E,
+            Command::SetCadParams => 0x88,
+            Command::SetBufferBaseAddress => 0x8F,
+            Command::SetModulationParams => 0x8B,
+            Command::SetPacketParams => 0x8C,
+            Command::GetRxBufferStatus => 0x17,
+            Command::GetPacketStatus => 0x1D,
+            Command::GetRssilnst => 0x1F,
+            Command::SetDioIrqParams => 0x8D,
+            Command::GetIrqStatus => 0x15,
+            Command::ClearIrqStatus => 0x97,
+            Command::SetRegulatorMode => 0x96,
+            Command::SetSaveContext => 0xD5,
+            Command::SetAutoFS => 0x9E,
+            Command::SetAutoTx => 0x98,
+            Command::SetLongPreamble => 0x9B,
+            Command::SetUartSpeed => 0x9D,
+            Command::SetRangingRole => 0xA3,
+            Command::SetAdvancedRanging => 0x9A,
+        }
+    }
+
+    // Mirrored registers the chip rewrites itself while executing this command. A new packet type
+    // loads that modem's defaults, so everything is re-read.
+    pub fn rewritten_registers(&self) -> &'static [Register] {
+        match *self {
+            Command::SetPacketType => &MIRRORED_REGISTERS,
+            Command::SetModulationParams | Command::SetPacketParams => {
+                &[Register::PayloadLength, Register::LoRaHeaderMode, Register::PacketPreambleSettings]
+            }
+            _ => &[],
         }
     }
 }
 
-enum Register {
+// Configuration and status registers of the SX1280 (datasheet table 13-1), plus the firmware
+// version word used by Semtech's reference driver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    FirmwareVersion,
     RxGain,
     ManualGainSetting,
     LNAGainValue,
@@ -89,72 +106,397 @@ enum Register {
     SynchPeakAttenuation,
     PayloadLength,
     LoRaHeaderMode,
-    RangingRequestAddress3,
-    RangingRequestAddress2,
-    RangingRequestAddress1,
-    RangingRequestAddress0,
-    RangingDeviceAddress3,
-    RangingDeviceAddress2,
-    RangingDeviceAddress1,
-    RangingDeviceAddress0,
+    RangingRequestAddress,
+    RangingDeviceAddress,
     RangingFilterWindowSize,
     ResetRangingFilter,
     RangingResultMUX,
     SFAdditionalConfiguration,
+    AdvancedRangingAddressSelect,
+    RangingCalibration,
+    RangingIdCheckLength,
+    FrequencyErrorCorrection,
+    LoRaSyncWord,
+    FeiByte,
+    AdvancedRangingAddress,
+    RangingResult,
+    RangingRssi,
+    FreezeRangingResult,
+    PacketPreambleSettings,
+    WhiteningInitialValue,
+    CrcPolynomialDefinition,
+    CrcPolynomialSeed,
+    CrcInitialValue,
+    SyncAddressControl,
+    SyncAddress1,
+    SyncAddress2,
+    SyncAddress3,
+    AccessAddress,
+}
 
-    
+impl Register {
+    // Address of the most significant byte; multi-byte registers are stored big-endian
+    pub fn address(&self) -> u16 {
+        match *self {
+            Register::FirmwareVersion => 0x0153,
+            Register::RxGain => 0x0891,
+            Register::ManualGainSetting => 0x0895,
+            Register::LNAGainValue => 0x089E,
+            Register::LNAGainControl => 0x089F,
+            Register::SynchPeakAttenuation => 0x08C2,
+            Register::PayloadLength => 0x0901,
+            Register::LoRaHeaderMode => 0x0903,
+            Register::RangingRequestAddress => 0x0912,
+            Register::RangingDeviceAddress => 0x0916,
+            Register::RangingFilterWindowSize => 0x091E,
+            Register::ResetRangingFilter => 0x0923,
+            Register::RangingResultMUX => 0x0924,
+            Register::SFAdditionalConfiguration => 0x0925,
+            Register::AdvancedRangingAddressSelect => 0x0927,
+            Register::RangingCalibration => 0x092B,
+            Register::RangingIdCheckLength => 0x0931,
+            Register::FrequencyErrorCorrection => 0x093C,
+            Register::LoRaSyncWord => 0x0944,
+            Register::FeiByte => 0x0954,
+            Register::AdvancedRangingAddress => 0x095F,
+            Register::RangingResult => 0x0961,
+            Register::RangingRssi => 0x0964,
+            Register::FreezeRangingResult => 0x097F,
+            Register::PacketPreambleSettings => 0x09C1,
+            Register::WhiteningInitialValue => 0x09C5,
+            Register::CrcPolynomialDefinition => 0x09C6,
+            Register::CrcPolynomialSeed => 0x09C7,
+            Register::CrcInitialValue => 0x09C8,
+            Register::SyncAddressControl => 0x09CD,
+            Register::SyncAddress1 => 0x09CE,
+            Register::SyncAddress2 => 0x09D3,
+            Register::SyncAddress3 => 0x09D8,
+            Register::AccessAddress => 0x09CF,
+        }
+    }
+
+    // Width in bytes
+    pub fn width(&self) -> usize {
+        match *self {
+            Register::FirmwareVersion
+            | Register::AdvancedRangingAddress
+            | Register::LoRaSyncWord
+            | Register::CrcPolynomialDefinition
+            | Register::CrcInitialValue => 2,
+            Register::RangingCalibration
+            | Register::FeiByte
+            | Register::RangingResult
+            | Register::CrcPolynomialSeed => 3,
+            Register::RangingRequestAddress
+            | Register::RangingDeviceAddress
+            | Register::AccessAddress => 4,
+            Register::SyncAddress1
+            | Register::SyncAddress2
+            | Register::SyncAddress3 => 5,
+            _ => 1,
+        }
+    }
 
+    // Registers whose contents are produced by the radio rather than configured by the host. The
+    // payload length is overwritten with the length of every LoRa packet received.
+    pub fn is_volatile(&self) -> bool {
+        matches!(
+            *self,
+            Register::FirmwareVersion
+                | Register::PayloadLength
+                | Register::ResetRangingFilter
+                | Register::FeiByte
+                | Register::RangingResult
+                | Register::RangingRssi
+                | Register::AdvancedRangingAddress
+        )
+    }
+
+    // Packs a big-endian register image into an integer
+    pub fn decode(&self, data: &[u8]) -> u64 {
+        data.iter().fold(0, |value, byte| (value << 8) | *byte as u64)
+    }
+
+    // Unpacks an integer into the big-endian register image
+    pub fn encode(&self, value: u64) -> Vec<u8> {
+        (0..self.width()).rev().map(|i| (value >> (8 * i)) as u8).collect()
+    }
 }
 
-const REG_ID_RXGAIN: u16 = 0x981;
-
-struct SX1280_registers {
-    rx_gain: u8,
-    manual_gain_setting: u8,
-    lna_gain_value: u8,
-    lna_gain_control: u8,
-    sync_peak_attenuation: u8,
-    payload_length: u8,
-    lora_header_mode: u8,
-    ranging_request_addr: [u8; 4],
-    ranging_device_addr: [u8; 4],
-    ranging_filter_window_size: u8,
-    reset_ranging_filter: u8,
-    ranging_result_mux: u8,
-    sf_additional_configuration: u8,
-    ranging_calibration_byte: [u8; 3],
-    ranging_id_check_length: u8,
-    frequency_error_correction: u8,
-    lora_sync_word: [u8; 2],
-    fei_byte: [u8; 3],
-    ranging_result_byte: [u8; 3],
-    ranging_rssi: u8,
-    freeze_ranging_result: u8,
-    packet_preamble_settings: u8,
-    whitening_initial_value: u8,
-    crc_polynomial_definition: u16,
-    crc_polynomial_seed: u32,
-    crc_initial_value: u16,
-    sync_address_control: u8,
-    sync_address_1: u64,
-    sync_address_2: u64,
-    sync_address_3: u64,
+// A group of bits inside a single-byte register
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub register: Register,
+    pub shift: u8,
+    pub width: u8,
 }
 
-struct SX1280 {
-    regs: SX1280_registers,
+impl Field {
+    pub const GAIN_MODE: Field = Field { register: Register::RxGain, shift: 6, width: 2 };
+    pub const AUTOMATIC_GAIN_CONTROL: Field = Field { register: Register::ManualGainSetting, shift: 0, width: 1 };
+    pub const LNA_GAIN: Field = Field { register: Register::LNAGainValue, shift: 0, width: 4 };
+    pub const MANUAL_GAIN_ENABLE: Field = Field { register: Register::LNAGainControl, shift: 7, width: 1 };
+    pub const SYNCH_PEAK_ATTENUATION: Field = Field { register: Register::SynchPeakAttenuation, shift: 3, width: 3 };
+    pub const LORA_IMPLICIT_HEADER: Field = Field { register: Register::LoRaHeaderMode, shift: 7, width: 1 };
+    pub const RANGING_FILTER_RESET: Field = Field { register: Register::ResetRangingFilter, shift: 6, width: 1 };
+    pub const RANGING_RESULT_MUX: Field = Field { register: Register::RangingResultMUX, shift: 4, width: 2 };
+    pub const RANGING_ID_CHECK_LENGTH: Field = Field { register: Register::RangingIdCheckLength, shift: 6, width: 2 };
+    pub const FREQUENCY_ERROR_CORRECTION: Field = Field { register: Register::FrequencyErrorCorrection, shift: 0, width: 3 };
+    pub const ADVANCED_RANGING_ADDRESS_SELECT: Field = Field { register: Register::AdvancedRangingAddressSelect, shift: 0, width: 2 };
+    pub const FREEZE_RANGING_RESULT: Field = Field { register: Register::FreezeRangingResult, shift: 1, width: 1 };
+    pub const SYNC_ADDRESS_ERROR_TOLERANCE: Field = Field { register: Register::SyncAddressControl, shift: 0, width: 4 };
+
+    pub fn mask(&self) -> u8 {
+        (((1u16 << self.width) - 1) << self.shift) as u8
+    }
+
+    pub fn extract(&self, byte: u8) -> u8 {
+        (byte & self.mask()) >> self.shift
+    }
+
+    pub fn insert(&self, byte: u8, value: u8) -> u8 {
+        (byte & !self.mask()) | ((value << self.shift) & self.mask())
+    }
 }
 
-impl SX1280 {
+// Registers mirrored in SX1280_registers, in field order
+pub const MIRRORED_REGISTERS: [Register; 30] = [
+    Register::RxGain,
+    Register::ManualGainSetting,
+    Register::LNAGainValue,
+    Register::LNAGainControl,
+    Register::SynchPeakAttenuation,
+    Register::PayloadLength,
+    Register::LoRaHeaderMode,
+    Register::RangingRequestAddress,
+    Register::RangingDeviceAddress,
+    Register::RangingFilterWindowSize,
+    Register::ResetRangingFilter,
+    Register::RangingResultMUX,
+    Register::SFAdditionalConfiguration,
+    Register::RangingCalibration,
+    Register::RangingIdCheckLength,
+    Register::FrequencyErrorCorrection,
+    Register::LoRaSyncWord,
+    Register::FeiByte,
+    Register::RangingResult,
+    Register::RangingRssi,
+    Register::FreezeRangingResult,
+    Register::PacketPreambleSettings,
+    Register::WhiteningInitialValue,
+    Register::CrcPolynomialDefinition,
+    Register::CrcPolynomialSeed,
+    Register::CrcInitialValue,
+    Register::SyncAddressControl,
+    Register::SyncAddress1,
+    Register::SyncAddress2,
+    Register::SyncAddress3,
+];
+
+// A mirrored register whose value on the chip no longer matches the shadow copy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisterDrift {
+    pub register: Register,
+    pub expected: u64,
+    pub actual: u64,
+}
 
-    fn WriteRegister(addr : u16, )
+#[allow(non_camel_case_types)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SX1280_registers {
+    pub rx_gain: u8,
+    pub manual_gain_setting: u8,
+    pub lna_gain_value: u8,
+    pub lna_gain_control: u8,
+    pub sync_peak_attenuation: u8,
+    pub payload_length: u8,
+    pub lora_header_mode: u8,
+    pub ranging_request_addr: [u8; 4],
+    pub ranging_device_addr: [u8; 4],
+    pub ranging_filter_window_size: u8,
+    pub reset_ranging_filter: u8,
+    pub ranging_result_mux: u8,
+    pub sf_additional_configuration: u8,
+    pub ranging_calibration_byte: [u8; 3],
+    pub ranging_id_check_length: u8,
+    pub frequency_error_correction: u8,
+    pub lora_sync_word: [u8; 2],
+    pub fei_byte: [u8; 3],
+    pub ranging_result_byte: [u8; 3],
+    pub ranging_rssi: u8,
+    pub freeze_ranging_result: u8,
+    pub packet_preamble_settings: u8,
+    pub whitening_initial_value: u8,
+    pub crc_polynomial_definition: u16,
+    pub crc_polynomial_seed: u32,
+    pub crc_initial_value: u16,
+    pub sync_address_control: u8,
+    pub sync_address_1: u64,
+    pub sync_address_2: u64,
+    pub sync_address_3: u64,
+}
+
+// Shadow copy of the chip's configuration registers, kept in step with every register write
+impl SX1280_registers {
+    pub fn get(&self, reg: Register) -> Option<u64> {
+        let value = match reg {
+            Register::RxGain => self.rx_gain as u64,
+            Register::ManualGainSetting => self.manual_gain_setting as u64,
+            Register::LNAGainValue => self.lna_gain_value as u64,
+            Register::LNAGainControl => self.lna_gain_control as u64,
+            Register::SynchPeakAttenuation => self.sync_peak_attenuation as u64,
+            Register::PayloadLength => self.payload_length as u64,
+            Register::LoRaHeaderMode => self.lora_header_mode as u64,
+            Register::RangingRequestAddress => reg.decode(&self.ranging_request_addr),
+            Register::RangingDeviceAddress => reg.decode(&self.ranging_device_addr),
+            Register::RangingFilterWindowSize => self.ranging_filter_window_size as u64,
+            Register::ResetRangingFilter => self.reset_ranging_filter as u64,
+            Register::RangingResultMUX => self.ranging_result_mux as u64,
+            Register::SFAdditionalConfiguration => self.sf_additional_configuration as u64,
+            Register::RangingCalibration => reg.decode(&self.ranging_calibration_byte),
+            Register::RangingIdCheckLength => self.ranging_id_check_length as u64,
+            Register::FrequencyErrorCorrection => self.frequency_error_correction as u64,
+            Register::LoRaSyncWord => reg.decode(&self.lora_sync_word),
+            Register::FeiByte => reg.decode(&self.fei_byte),
+            Register::RangingResult => reg.decode(&self.ranging_result_byte),
+            Register::RangingRssi => self.ranging_rssi as u64,
+            Register::FreezeRangingResult => self.freeze_ranging_result as u64,
+            Register::PacketPreambleSettings => self.packet_preamble_settings as u64,
+            Register::WhiteningInitialValue => self.whitening_initial_value as u64,
+            Register::CrcPolynomialDefinition => self.crc_polynomial_definition as u64,
+            Register::CrcPolynomialSeed => self.crc_polynomial_seed as u64,
+            Register::CrcInitialValue => self.crc_initial_value as u64,
+            Register::SyncAddressControl => self.sync_address_control as u64,
+            Register::SyncAddress1 => self.sync_address_1,
+            Register::SyncAddress2 => self.sync_address_2,
+            Register::SyncAddress3 => self.sync_address_3,
+            Register::FirmwareVersion
+            | Register::AdvancedRangingAddressSelect
+            | Register::AdvancedRangingAddress
+            | Register::AccessAddress => return None,
+        };
+        Some(value)
+    }
+
+    pub fn set(&mut self, reg: Register, value: u64) {
+        let bytes = reg.encode(value);
+        match reg {
+            Register::RxGain => self.rx_gain = value as u8,
+            Register::ManualGainSetting => self.manual_gain_setting = value as u8,
+            Register::LNAGainValue => self.lna_gain_value = value as u8,
+            Register::LNAGainControl => self.lna_gain_control = value as u8,
+            Register::SynchPeakAttenuation => self.sync_peak_attenuation = value as u8,
+            Register::PayloadLength => self.payload_length = value as u8,
+            Register::LoRaHeaderMode => self.lora_header_mode = value as u8,
+            Register::RangingRequestAddress => self.ranging_request_addr.copy_from_slice(&bytes),
+            Register::RangingDeviceAddress => self.ranging_device_addr.copy_from_slice(&bytes),
+            Register::RangingFilterWindowSize => self.ranging_filter_window_size = value as u8,
+            Register::ResetRangingFilter => self.reset_ranging_filter = value as u8,
+            Register::RangingResultMUX => self.ranging_result_mux = value as u8,
+            Register::SFAdditionalConfiguration => self.sf_additional_configuration = value as u8,
+            Register::RangingCalibration => self.ranging_calibration_byte.copy_from_slice(&bytes),
+            Register::RangingIdCheckLength => self.ranging_id_check_length = value as u8,
+            Register::FrequencyErrorCorrection => self.frequency_error_correction = value as u8,
+            Register::LoRaSyncWord => self.lora_sync_word.copy_from_slice(&bytes),
+            Register::FeiByte => self.fei_byte.copy_from_slice(&bytes),
+            Register::RangingResult => self.ranging_result_byte.copy_from_slice(&bytes),
+            Register::RangingRssi => self.ranging_rssi = value as u8,
+            Register::FreezeRangingResult => self.freeze_ranging_result = value as u8,
+            Register::PacketPreambleSettings => self.packet_preamble_settings = value as u8,
+            Register::WhiteningInitialValue => self.whitening_initial_value = value as u8,
+            Register::CrcPolynomialDefinition => self.crc_polynomial_definition = value as u16,
+            Register::CrcPolynomialSeed => self.crc_polynomial_seed = (value & 0xFF_FFFF) as u32,
+            Register::CrcInitialValue => self.crc_initial_value = value as u16,
+            Register::SyncAddressControl => self.sync_address_control = value as u8,
+            Register::SyncAddress1 => self.sync_address_1 = value & 0xFF_FFFF_FFFF,
+            Register::SyncAddress2 => self.sync_address_2 = value & 0xFF_FFFF_FFFF,
+            Register::SyncAddress3 => self.sync_address_3 = value & 0xFF_FFFF_FFFF,
+            Register::FirmwareVersion
+            | Register::AdvancedRangingAddressSelect
+            | Register::AdvancedRangingAddress
+            | Register::AccessAddress => (),
+        }
+    }
+
+    // Applies a raw write of data at addr to every mirrored register it touches. Several registers
+    // share addresses (e.g. the CRC seed and initial value), so this works byte by byte.
+    pub fn update(&mut self, addr: u16, data: &[u8]) {
+        let start = addr as usize;
+        let end = start + data.len();
+
+        for reg in MIRRORED_REGISTERS.iter() {
+            let reg_start = reg.address() as usize;
+            let reg_end = reg_start + reg.width();
+            if reg_end <= start || end <= reg_start {
+                continue;
+            }
+
+            let mut bytes = reg.encode(self.get(*reg).unwrap_or(0));
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                let a = reg_start + i;
+                if (start..end).contains(&a) {
+                    *byte = data[a - start];
+                }
+            }
+            self.set(*reg, reg.decode(&bytes));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_multi_byte_register() {
+        let mut regs = SX1280_registers::default();
+        regs.update(0x09CE, &[0x12, 0x34, 0x56, 0x78, 0x9A]);
+        assert_eq!(regs.sync_address_1, 0x12_3456_789A);
+
+        // a partial write keeps the bytes it doesn't cover
+        regs.update(0x09D0, &[0xAB, 0xCD]);
+        assert_eq!(regs.sync_address_1, 0x12_34AB_CD9A);
+    }
+
+    #[test]
+    fn update_spanning_several_registers() {
+        let mut regs = SX1280_registers::default();
+        regs.update(0x09D1, &[0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(regs.sync_address_1, 0x00_0000_0102);
+        assert_eq!(regs.sync_address_2, 0x03_0400_0000);
+    }
+
+    #[test]
+    fn update_overlapping_registers() {
+        let mut regs = SX1280_registers::default();
+        // the CRC seed shares its low two bytes with the initial value, and its top byte with the
+        // low byte of the polynomial
+        regs.update(0x09C7, &[0xAA, 0xBB, 0xCC]);
+        assert_eq!(regs.crc_polynomial_seed, 0xAA_BBCC);
+        assert_eq!(regs.crc_initial_value, 0xBBCC);
+        assert_eq!(regs.crc_polynomial_definition, 0x00AA);
+
+        regs.update(0x09C6, &[0x10, 0x21]);
+        assert_eq!(regs.crc_polynomial_definition, 0x1021);
+        assert_eq!(regs.crc_polynomial_seed, 0x21_BBCC);
+        assert_eq!(regs.crc_initial_value, 0xBBCC);
+
+        regs.update(0x09C9, &[0xEE]);
+        assert_eq!(regs.crc_polynomial_seed, 0x21_BBEE);
+        assert_eq!(regs.crc_initial_value, 0xBBEE);
+    }
+
+    #[test]
+    fn packet_type_change_rereads_every_mirrored_register() {
+        assert_eq!(Command::SetPacketType.rewritten_registers(), &MIRRORED_REGISTERS[..]);
+        assert!(Command::SetPacketParams.rewritten_registers().contains(&Register::LoRaHeaderMode));
+        assert!(Command::SetRfFrequency.rewritten_registers().is_empty());
+    }
 
-    fn SetModulationParams(modParam1: u8, modParam2: u8, modParam3: u8) -> i16 {
-        let data: [u8, 3] = [modParam1, modParam2, modParam3];
-        // return(self::mod->SPIwriteStream(SX128X_CMD_SET_MODULATION_PARAMS, data, 3));
-        // self::mod::SPIwriteStream(SX1280_CMD_SET_MODULATION_PARAMS, data, 3)
-        // (write - master out/slave in pin 2, read - master in/slave out pin 3, chip_select pin 4 -> 3 chip selects)
-        return 0
+    #[test]
+    fn update_ignores_unmirrored_addresses() {
+        let mut regs = SX1280_registers::default();
+        regs.update(0x0153, &[0xA9, 0xB5]);
+        assert_eq!(regs, SX1280_registers::default());
     }
-    
 }
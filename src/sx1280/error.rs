@@ -0,0 +1,84 @@
+use std::{fmt, io};
+use std::time::Duration;
+
+use super::device::Command;
+use super::status::Status;
+
+// Errors reported by the SX1280 driver. Each variant carries the value that was rejected.
+#[derive(Debug)]
+pub enum Sx1280Error {
+    InvalidBandwidth(f32),
+    InvalidSpreadingFactor(u8),
+    InvalidCodingRate(u8),
+    InvalidFrequency(f32),
+    InvalidOutputPower(i8),
+    InvalidPreambleLength(u32),
+    InvalidFrequencyDeviation(f32),
+    InvalidSyncWord(usize),
+    InvalidCrcConfiguration(u8),
+    InvalidPayloadLength(usize),
+    InvalidChannel(u8),
+    InvalidTimeout(Duration),
+    InvalidRangingConfiguration(u8),
+    InvalidCadSymbols(u8),
+    WrongModem(u8),
+    Spi(io::Error),
+    BusyTimeout(Duration),
+    CommandStatus(Command, Status),
+    ChipNotFound(u16),
+    NoIrqPin,
+    TxTimeout,
+    CadTimeout,
+    ChannelBusy(u32),
+    CrcError,
+    HeaderError,
+}
+
+pub type Result<T> = std::result::Result<T, Sx1280Error>;
+
+impl fmt::Display for Sx1280Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sx1280Error::InvalidBandwidth(bw) => write!(f, "invalid bandwidth: {} kHz", bw),
+            Sx1280Error::InvalidSpreadingFactor(sf) => write!(f, "invalid spreading factor: SF{}", sf),
+            Sx1280Error::InvalidCodingRate(cr) => write!(f, "invalid coding rate: {}", cr),
+            Sx1280Error::InvalidFrequency(freq) => write!(f, "invalid frequency: {} MHz", freq),
+            Sx1280Error::InvalidOutputPower(pwr) => write!(f, "invalid output power: {} dBm", pwr),
+            Sx1280Error::InvalidPreambleLength(len) => write!(f, "invalid preamble length: {}", len),
+            Sx1280Error::InvalidFrequencyDeviation(dev) => write!(f, "invalid frequency deviation: {} kHz", dev),
+            Sx1280Error::InvalidSyncWord(len) => write!(f, "invalid sync word length: {} bytes", len),
+            Sx1280Error::InvalidCrcConfiguration(len) => write!(f, "invalid CRC length: {} bytes", len),
+            Sx1280Error::InvalidPayloadLength(len) => write!(f, "invalid payload length: {} bytes", len),
+            Sx1280Error::InvalidChannel(channel) => write!(f, "invalid channel: {}", channel),
+            Sx1280Error::InvalidTimeout(timeout) => write!(f, "invalid timeout: {:?}", timeout),
+            Sx1280Error::InvalidRangingConfiguration(value) => write!(f, "invalid ranging configuration value: {}", value),
+            Sx1280Error::InvalidCadSymbols(symbols) => write!(f, "invalid CAD length: {} symbols", symbols),
+            Sx1280Error::WrongModem(modem) => write!(f, "operation not supported by packet type {:#04x}", modem),
+            Sx1280Error::Spi(err) => write!(f, "SPI transfer failed: {}", err),
+            Sx1280Error::BusyTimeout(timeout) => write!(f, "BUSY still high after {:?}", timeout),
+            Sx1280Error::CommandStatus(cmd, status) => write!(f, "{:?} failed with status {}", cmd, status),
+            Sx1280Error::ChipNotFound(version) => write!(f, "no SX1280 found, firmware version read as {:#06x}", version),
+            Sx1280Error::NoIrqPin => write!(f, "no IRQ pin configured"),
+            Sx1280Error::TxTimeout => write!(f, "transmission timed out"),
+            Sx1280Error::CadTimeout => write!(f, "channel activity detection timed out"),
+            Sx1280Error::ChannelBusy(attempts) => write!(f, "channel still busy after {} attempts", attempts),
+            Sx1280Error::CrcError => write!(f, "packet received with CRC error"),
+            Sx1280Error::HeaderError => write!(f, "packet received with header error"),
+        }
+    }
+}
+
+impl std::error::Error for Sx1280Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Sx1280Error::Spi(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Sx1280Error {
+    fn from(err: io::Error) -> Self {
+        Sx1280Error::Spi(err)
+    }
+}
@@ -0,0 +1,17 @@
+use super::irq::Irq;
+use super::packet::RxPacket;
+
+// What the IRQs behind one rising edge of the IRQ pin amounted to, see SX1280::event_loop
+#[derive(Clone, Debug, PartialEq)]
+pub enum RadioEvent {
+    TxDone,
+    RxDone(RxPacket),
+    CrcError,
+    HeaderError,
+    // the chip's own RX or TX timeout ran out
+    Timeout,
+    CadDone { detected: bool },
+    PreambleDetected,
+    // anything else routed to the pin, e.g. the ranging IRQs
+    Other(Irq),
+}
@@ -0,0 +1,227 @@
+use super::error::{Result, Sx1280Error};
+
+// SX1280_CMD_SET_MODULATION_PARAMS, FLRC bit rate and bandwidth
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlrcBitRate {
+    Kbps260,
+    Kbps325,
+    Kbps520,
+    Kbps650,
+    Kbps1040,
+    Kbps1300,
+}
+
+impl FlrcBitRate {
+    pub fn param(&self) -> u8 {
+        match *self {
+            FlrcBitRate::Kbps260 => 0xEB,
+            FlrcBitRate::Kbps325 => 0xC7,
+            FlrcBitRate::Kbps520 => 0xAA,
+            FlrcBitRate::Kbps650 => 0x86,
+            FlrcBitRate::Kbps1040 => 0x69,
+            FlrcBitRate::Kbps1300 => 0x45,
+        }
+    }
+
+    pub fn bits_per_second(&self) -> u32 {
+        match *self {
+            FlrcBitRate::Kbps260 => 260_000,
+            FlrcBitRate::Kbps325 => 325_000,
+            FlrcBitRate::Kbps520 => 520_000,
+            FlrcBitRate::Kbps650 => 650_000,
+            FlrcBitRate::Kbps1040 => 1_040_000,
+            FlrcBitRate::Kbps1300 => 1_300_000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlrcCodingRate {
+    Cr1_2,
+    Cr3_4,
+    Cr1_0,
+}
+
+impl FlrcCodingRate {
+    pub fn param(&self) -> u8 {
+        match *self {
+            FlrcCodingRate::Cr1_2 => 0x00,
+            FlrcCodingRate::Cr3_4 => 0x02,
+            FlrcCodingRate::Cr1_0 => 0x04,
+        }
+    }
+
+    pub fn ratio(&self) -> f32 {
+        match *self {
+            FlrcCodingRate::Cr1_2 => 0.5,
+            FlrcCodingRate::Cr3_4 => 0.75,
+            FlrcCodingRate::Cr1_0 => 1.0,
+        }
+    }
+}
+
+// Gaussian filter bandwidth-time product, shared by GFSK, BLE and FLRC
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shaping {
+    Off,
+    Bt1_0,
+    Bt0_5,
+}
+
+impl Shaping {
+    pub fn param(&self) -> u8 {
+        match *self {
+            Shaping::Off => 0x00,
+            Shaping::Bt1_0 => 0x10,
+            Shaping::Bt0_5 => 0x20,
+        }
+    }
+}
+
+// SX1280_CMD_SET_PACKET_PARAMS, which of the three sync words a GFSK/FLRC receiver accepts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncWordMatch {
+    Off,
+    Sync1,
+    Sync2,
+    Sync1Or2,
+    Sync3,
+    Sync1Or3,
+    Sync2Or3,
+    Any,
+}
+
+impl SyncWordMatch {
+    pub fn param(&self) -> u8 {
+        match *self {
+            SyncWordMatch::Off => 0x00,
+            SyncWordMatch::Sync1 => 0x10,
+            SyncWordMatch::Sync2 => 0x20,
+            SyncWordMatch::Sync1Or2 => 0x30,
+            SyncWordMatch::Sync3 => 0x40,
+            SyncWordMatch::Sync1Or3 => 0x50,
+            SyncWordMatch::Sync2Or3 => 0x60,
+            SyncWordMatch::Any => 0x70,
+        }
+    }
+}
+
+// GFSK/FLRC header: variable length packets carry their length, fixed length ones do not
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketLength {
+    Variable,
+    Fixed(u8),
+}
+
+impl PacketLength {
+    pub fn param(&self) -> u8 {
+        match *self {
+            PacketLength::Fixed(_) => 0x00,
+            PacketLength::Variable => 0x20,
+        }
+    }
+
+    // Longest packet the receiver accepts with the packet parameters set up for payload_length bytes
+    pub fn max_rx_length(&self, payload_length: usize) -> usize {
+        match *self {
+            PacketLength::Variable => payload_length,
+            PacketLength::Fixed(length) => length as usize,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlrcCrc {
+    Off,
+    Bytes2,
+    Bytes3,
+    Bytes4,
+}
+
+impl FlrcCrc {
+    pub fn param(&self) -> u8 {
+        match *self {
+            FlrcCrc::Off => 0x00,
+            FlrcCrc::Bytes2 => 0x10,
+            FlrcCrc::Bytes3 => 0x20,
+            FlrcCrc::Bytes4 => 0x30,
+        }
+    }
+
+    pub fn bytes(&self) -> u8 {
+        match *self {
+            FlrcCrc::Off => 0,
+            FlrcCrc::Bytes2 => 2,
+            FlrcCrc::Bytes3 => 3,
+            FlrcCrc::Bytes4 => 4,
+        }
+    }
+}
+
+pub const FLRC_MIN_PAYLOAD_LENGTH: u8 = 6;
+pub const FLRC_MAX_PAYLOAD_LENGTH: u8 = 127;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlrcConfig {
+    pub bit_rate: FlrcBitRate,
+    pub cr: FlrcCodingRate,
+    pub shaping: Shaping,
+    // preamble length in bits, 8 to 32 in steps of 4
+    pub preamble_bits: u8,
+    // 32-bit sync words 1 to 3; only the ones selected by sync_match are used
+    pub sync_words: [u32; 3],
+    pub sync_match: SyncWordMatch,
+    pub length: PacketLength,
+    pub crc: FlrcCrc,
+}
+
+impl Default for FlrcConfig {
+    fn default() -> Self {
+        FlrcConfig {
+            bit_rate: FlrcBitRate::Kbps650,
+            cr: FlrcCodingRate::Cr3_4,
+            shaping: Shaping::Bt0_5,
+            preamble_bits: 32,
+            sync_words: [0x2D01_4B1D, 0, 0],
+            sync_match: SyncWordMatch::Sync1,
+            length: PacketLength::Variable,
+            crc: FlrcCrc::Bytes2,
+        }
+    }
+}
+
+impl FlrcConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(8..=32).contains(&self.preamble_bits) || !self.preamble_bits.is_multiple_of(4) {
+            return Err(Sx1280Error::InvalidPreambleLength(self.preamble_bits as u32));
+        }
+        if let PacketLength::Fixed(length) = self.length {
+            if !(FLRC_MIN_PAYLOAD_LENGTH..=FLRC_MAX_PAYLOAD_LENGTH).contains(&length) {
+                return Err(Sx1280Error::InvalidPayloadLength(length as usize));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn modulation_params(&self) -> [u8; 3] {
+        [self.bit_rate.param(), self.cr.param(), self.shaping.param()]
+    }
+
+    pub fn packet_params(&self, payload_length: u8) -> [u8; 7] {
+        let length = match self.length {
+            PacketLength::Variable => payload_length.min(FLRC_MAX_PAYLOAD_LENGTH),
+            PacketLength::Fixed(length) => length,
+        };
+        [
+            ((self.preamble_bits / 4) - 1) << 4,
+            // FLRC only knows "no sync word" or a 32-bit one
+            if self.sync_match == SyncWordMatch::Off { 0x00 } else { 0x04 },
+            self.sync_match.param(),
+            self.length.param(),
+            length,
+            self.crc.param(),
+            // whitening is not available in FLRC and must be disabled
+            0x08,
+        ]
+    }
+}
@@ -0,0 +1,180 @@
+use super::error::{Result, Sx1280Error};
+use super::flrc::{PacketLength, Shaping, SyncWordMatch};
+
+// SX1280_CMD_SET_MODULATION_PARAMS, GFSK/BLE bit rate and the receiver bandwidth paired with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GfskBitRate {
+    Br2000Bw2400,
+    Br1600Bw2400,
+    Br1000Bw2400,
+    Br1000Bw1200,
+    Br800Bw2400,
+    Br800Bw1200,
+    Br500Bw1200,
+    Br500Bw600,
+    Br400Bw1200,
+    Br400Bw600,
+    Br250Bw600,
+    Br250Bw300,
+    Br125Bw300,
+}
+
+impl GfskBitRate {
+    pub fn param(&self) -> u8 {
+        match *self {
+            GfskBitRate::Br2000Bw2400 => 0x04,
+            GfskBitRate::Br1600Bw2400 => 0x28,
+            GfskBitRate::Br1000Bw2400 => 0x4C,
+            GfskBitRate::Br1000Bw1200 => 0x45,
+            GfskBitRate::Br800Bw2400 => 0x70,
+            GfskBitRate::Br800Bw1200 => 0x69,
+            GfskBitRate::Br500Bw1200 => 0x8D,
+            GfskBitRate::Br500Bw600 => 0x86,
+            GfskBitRate::Br400Bw1200 => 0xB1,
+            GfskBitRate::Br400Bw600 => 0xAA,
+            GfskBitRate::Br250Bw600 => 0xCE,
+            GfskBitRate::Br250Bw300 => 0xC7,
+            GfskBitRate::Br125Bw300 => 0xEF,
+        }
+    }
+
+    pub fn kbps(&self) -> f32 {
+        match *self {
+            GfskBitRate::Br2000Bw2400 => 2000.0,
+            GfskBitRate::Br1600Bw2400 => 1600.0,
+            GfskBitRate::Br1000Bw2400 | GfskBitRate::Br1000Bw1200 => 1000.0,
+            GfskBitRate::Br800Bw2400 | GfskBitRate::Br800Bw1200 => 800.0,
+            GfskBitRate::Br500Bw1200 | GfskBitRate::Br500Bw600 => 500.0,
+            GfskBitRate::Br400Bw1200 | GfskBitRate::Br400Bw600 => 400.0,
+            GfskBitRate::Br250Bw600 | GfskBitRate::Br250Bw300 => 250.0,
+            GfskBitRate::Br125Bw300 => 125.0,
+        }
+    }
+
+    pub fn bandwidth_khz(&self) -> f32 {
+        match *self {
+            GfskBitRate::Br2000Bw2400
+            | GfskBitRate::Br1600Bw2400
+            | GfskBitRate::Br1000Bw2400
+            | GfskBitRate::Br800Bw2400 => 2400.0,
+            GfskBitRate::Br1000Bw1200
+            | GfskBitRate::Br800Bw1200
+            | GfskBitRate::Br500Bw1200
+            | GfskBitRate::Br400Bw1200 => 1200.0,
+            GfskBitRate::Br500Bw600 | GfskBitRate::Br400Bw600 | GfskBitRate::Br250Bw600 => 600.0,
+            GfskBitRate::Br250Bw300 | GfskBitRate::Br125Bw300 => 300.0,
+        }
+    }
+}
+
+// Modulation index code for a frequency deviation in kHz at the given bit rate. The chip supports
+// 0.35 and then 0.5 to 4.0 in steps of 0.25; the nearest one is used.
+pub fn modulation_index(deviation_khz: f32, bit_rate: GfskBitRate) -> Result<u8> {
+    let index = 2.0 * deviation_khz / bit_rate.kbps();
+    if !(0.25..4.125).contains(&index) {
+        return Err(Sx1280Error::InvalidFrequencyDeviation(deviation_khz));
+    }
+    if index < 0.425 {
+        return Ok(0x00);
+    }
+    Ok((index * 4.0).round() as u8 - 1)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GfskCrc {
+    Off,
+    Bytes1,
+    Bytes2,
+}
+
+impl GfskCrc {
+    pub fn param(&self) -> u8 {
+        match *self {
+            GfskCrc::Off => 0x00,
+            GfskCrc::Bytes1 => 0x10,
+            GfskCrc::Bytes2 => 0x20,
+        }
+    }
+
+    pub fn bytes(&self) -> u8 {
+        match *self {
+            GfskCrc::Off => 0,
+            GfskCrc::Bytes1 => 1,
+            GfskCrc::Bytes2 => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GfskConfig {
+    pub bit_rate: GfskBitRate,
+    // frequency deviation in kHz, turned into a modulation index against bit_rate
+    pub deviation: f32,
+    pub shaping: Shaping,
+    // preamble length in bits, 4 to 32 in steps of 4
+    pub preamble_bits: u8,
+    // sync words 1 to 3, right-aligned in sync_word_length bytes (1 to 5)
+    pub sync_words: [u64; 3],
+    pub sync_word_length: u8,
+    pub sync_match: SyncWordMatch,
+    pub length: PacketLength,
+    pub crc: GfskCrc,
+    pub crc_polynomial: u16,
+    pub crc_seed: u16,
+    pub whitening: bool,
+}
+
+impl Default for GfskConfig {
+    fn default() -> Self {
+        GfskConfig {
+            bit_rate: GfskBitRate::Br800Bw2400,
+            deviation: 400.0,
+            shaping: Shaping::Bt0_5,
+            preamble_bits: 32,
+            sync_words: [0x12AD, 0, 0],
+            sync_word_length: 2,
+            sync_match: SyncWordMatch::Sync1,
+            length: PacketLength::Variable,
+            crc: GfskCrc::Bytes2,
+            crc_polynomial: 0x1021,
+            crc_seed: 0x1D0F,
+            whitening: true,
+        }
+    }
+}
+
+impl GfskConfig {
+    pub fn validate(&self) -> Result<()> {
+        modulation_index(self.deviation, self.bit_rate)?;
+
+        if !(4..=32).contains(&self.preamble_bits) || !self.preamble_bits.is_multiple_of(4) {
+            return Err(Sx1280Error::InvalidPreambleLength(self.preamble_bits as u32));
+        }
+
+        let length = self.sync_word_length as usize;
+        if !(1..=5).contains(&length) || self.sync_words.iter().any(|word| *word >> (8 * length) != 0) {
+            return Err(Sx1280Error::InvalidSyncWord(length));
+        }
+        Ok(())
+    }
+
+    pub fn modulation_params(&self) -> Result<[u8; 3]> {
+        Ok([self.bit_rate.param(), modulation_index(self.deviation, self.bit_rate)?, self.shaping.param()])
+    }
+
+    pub fn packet_params(&self, payload_length: u8) -> [u8; 7] {
+        let length = match self.length {
+            PacketLength::Variable => payload_length,
+            PacketLength::Fixed(length) => length,
+        };
+        [
+            ((self.preamble_bits / 4) - 1) << 4,
+            (self.sync_word_length - 1) << 1,
+            self.sync_match.param(),
+            self.length.param(),
+            length,
+            self.crc.param(),
+            if self.whitening { 0x00 } else { 0x08 },
+        ]
+    }
+}
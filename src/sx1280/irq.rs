@@ -0,0 +1,155 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+// Set of SX1280 interrupt sources, as used by SetDioIrqParams, GetIrqStatus and ClearIrqStatus
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Irq(u16);
+
+impl Irq {
+    pub const NONE: Irq = Irq(0);
+    pub const TX_DONE: Irq = Irq(1 << 0);
+    pub const RX_DONE: Irq = Irq(1 << 1);
+    pub const SYNC_WORD_VALID: Irq = Irq(1 << 2);
+    pub const SYNC_WORD_ERROR: Irq = Irq(1 << 3);
+    pub const HEADER_VALID: Irq = Irq(1 << 4);
+    pub const HEADER_ERROR: Irq = Irq(1 << 5);
+    pub const CRC_ERROR: Irq = Irq(1 << 6);
+    pub const RANGING_SLAVE_RESPONSE_DONE: Irq = Irq(1 << 7);
+    pub const RANGING_SLAVE_REQUEST_DISCARD: Irq = Irq(1 << 8);
+    pub const RANGING_MASTER_RESULT_VALID: Irq = Irq(1 << 9);
+    pub const RANGING_MASTER_TIMEOUT: Irq = Irq(1 << 10);
+    pub const RANGING_SLAVE_REQUEST_VALID: Irq = Irq(1 << 11);
+    pub const CAD_DONE: Irq = Irq(1 << 12);
+    pub const CAD_DETECTED: Irq = Irq(1 << 13);
+    pub const RX_TX_TIMEOUT: Irq = Irq(1 << 14);
+    // bit 15 means PreambleDetected, or AdvancedRangingDone while advanced ranging is enabled
+    pub const PREAMBLE_DETECTED: Irq = Irq(1 << 15);
+    pub const ADVANCED_RANGING_DONE: Irq = Irq(1 << 15);
+    pub const ALL: Irq = Irq(0xFFFF);
+
+    const NAMES: [&'static str; 16] = [
+        "TxDone",
+        "RxDone",
+        "SyncWordValid",
+        "SyncWordError",
+        "HeaderValid",
+        "HeaderError",
+        "CrcError",
+        "RangingSlaveResponseDone",
+        "RangingSlaveRequestDiscard",
+        "RangingMasterResultValid",
+        "RangingMasterTimeout",
+        "RangingSlaveRequestValid",
+        "CadDone",
+        "CadDetected",
+        "RxTxTimeout",
+        "PreambleDetected",
+    ];
+
+    pub const fn from_bits(bits: u16) -> Irq {
+        Irq(bits)
+    }
+
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    // Every flag in other is set
+    pub const fn contains(&self, other: Irq) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // Same as |, but usable in constants
+    pub const fn union(self, other: Irq) -> Irq {
+        Irq(self.0 | other.0)
+    }
+
+    // At least one flag in other is set
+    pub const fn intersects(&self, other: Irq) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for Irq {
+    type Output = Irq;
+
+    fn bitor(self, rhs: Irq) -> Irq {
+        Irq(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Irq {
+    fn bitor_assign(&mut self, rhs: Irq) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Irq {
+    type Output = Irq;
+
+    fn bitand(self, rhs: Irq) -> Irq {
+        Irq(self.0 & rhs.0)
+    }
+}
+
+impl Not for Irq {
+    type Output = Irq;
+
+    fn not(self) -> Irq {
+        Irq(!self.0)
+    }
+}
+
+impl fmt::Debug for Irq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "Irq(None)");
+        }
+        let names: Vec<&str> = (0..16).filter(|bit| self.0 & (1 << bit) != 0).map(|bit| Irq::NAMES[bit]).collect();
+        write!(f, "Irq({})", names.join(" | "))
+    }
+}
+
+// The three DIO pins an IRQ can be routed to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dio {
+    Dio1,
+    Dio2,
+    Dio3,
+}
+
+// Which IRQs raise each DIO pin. An IRQ only reaches a pin if it is also enabled in the IRQ mask.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DioMapping {
+    pub dio1: Irq,
+    pub dio2: Irq,
+    pub dio3: Irq,
+}
+
+impl DioMapping {
+    pub fn get(&self, dio: Dio) -> Irq {
+        match dio {
+            Dio::Dio1 => self.dio1,
+            Dio::Dio2 => self.dio2,
+            Dio::Dio3 => self.dio3,
+        }
+    }
+
+    // Adds irq to the set that raises dio
+    pub fn route(&mut self, dio: Dio, irq: Irq) {
+        match dio {
+            Dio::Dio1 => self.dio1 |= irq,
+            Dio::Dio2 => self.dio2 |= irq,
+            Dio::Dio3 => self.dio3 |= irq,
+        }
+    }
+
+    // Every IRQ routed to any pin
+    pub fn all(&self) -> Irq {
+        self.dio1 | self.dio2 | self.dio3
+    }
+}
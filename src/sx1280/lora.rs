@@ -0,0 +1,323 @@
+use std::time::Duration;
+
+use super::error::{Result, Sx1280Error};
+
+// SX1280_CMD_SET_MODULATION_PARAMS, LoRa and ranging
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpreadingFactor {
+    Sf5 = 5,
+    Sf6 = 6,
+    Sf7 = 7,
+    Sf8 = 8,
+    Sf9 = 9,
+    Sf10 = 10,
+    Sf11 = 11,
+    Sf12 = 12,
+}
+
+impl SpreadingFactor {
+    pub fn value(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn param(&self) -> u8 {
+        self.value() << 4
+    }
+
+    // Value for Register::SFAdditionalConfiguration - SX1280 datasheet v3.0 section 13.4.1
+    pub fn additional_configuration(&self) -> u8 {
+        match *self {
+            SpreadingFactor::Sf5 | SpreadingFactor::Sf6 => 0x1E,
+            SpreadingFactor::Sf7 | SpreadingFactor::Sf8 => 0x37,
+            _ => 0x32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bandwidth {
+    Khz203_125,
+    Khz406_25,
+    Khz812_5,
+    Khz1625,
+}
+
+impl Bandwidth {
+    pub fn param(&self) -> u8 {
+        match *self {
+            Bandwidth::Khz203_125 => 0x34,
+            Bandwidth::Khz406_25 => 0x26,
+            Bandwidth::Khz812_5 => 0x18,
+            Bandwidth::Khz1625 => 0x0A,
+        }
+    }
+
+    pub fn khz(&self) -> f32 {
+        match *self {
+            Bandwidth::Khz203_125 => 203.125,
+            Bandwidth::Khz406_25 => 406.25,
+            Bandwidth::Khz812_5 => 812.5,
+            Bandwidth::Khz1625 => 1625.0,
+        }
+    }
+}
+
+// There is no long interleaved 4/7, so that combination cannot be expressed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodingRate {
+    Cr4_5 { long_interleave: bool },
+    Cr4_6 { long_interleave: bool },
+    Cr4_7,
+    Cr4_8 { long_interleave: bool },
+}
+
+impl CodingRate {
+    pub fn param(&self) -> u8 {
+        match *self {
+            CodingRate::Cr4_5 { long_interleave: false } => 0x01,
+            CodingRate::Cr4_6 { long_interleave: false } => 0x02,
+            CodingRate::Cr4_7 => 0x03,
+            CodingRate::Cr4_8 { long_interleave: false } => 0x04,
+            CodingRate::Cr4_5 { long_interleave: true } => 0x05,
+            CodingRate::Cr4_6 { long_interleave: true } => 0x06,
+            CodingRate::Cr4_8 { long_interleave: true } => 0x07,
+        }
+    }
+
+    // Denominator of the code rate, i.e. coded bits per 4 data bits
+    pub fn denominator(&self) -> u8 {
+        match *self {
+            CodingRate::Cr4_5 { .. } => 5,
+            CodingRate::Cr4_6 { .. } => 6,
+            CodingRate::Cr4_7 => 7,
+            CodingRate::Cr4_8 { .. } => 8,
+        }
+    }
+
+    pub fn is_long_interleaved(&self) -> bool {
+        matches!(
+            *self,
+            CodingRate::Cr4_5 { long_interleave: true }
+                | CodingRate::Cr4_6 { long_interleave: true }
+                | CodingRate::Cr4_8 { long_interleave: true }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoRaHeader {
+    Explicit,
+    // Implicit header packets all have the given payload length
+    Implicit(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoRaConfig {
+    pub sf: SpreadingFactor,
+    pub bw: Bandwidth,
+    pub cr: CodingRate,
+    // preamble length in symbols, rounded up to the next length the chip can encode
+    pub preamble: u32,
+    pub header: LoRaHeader,
+    pub crc: bool,
+    pub invert_iq: bool,
+}
+
+impl Default for LoRaConfig {
+    fn default() -> Self {
+        LoRaConfig {
+            sf: SpreadingFactor::Sf9,
+            bw: Bandwidth::Khz812_5,
+            cr: CodingRate::Cr4_7,
+            preamble: 12,
+            header: LoRaHeader::Explicit,
+            crc: true,
+            invert_iq: false,
+        }
+    }
+}
+
+impl LoRaConfig {
+    // Rejects combinations the chip cannot run, before anything is sent to it
+    pub fn validate(&self, ranging: bool) -> Result<()> {
+        encode_preamble(self.preamble)?;
+        if let LoRaHeader::Implicit(length) = self.header {
+            if length > self.max_payload_length() {
+                return Err(Sx1280Error::InvalidPayloadLength(length as usize));
+            }
+        }
+
+        if ranging {
+            if matches!(self.sf, SpreadingFactor::Sf11 | SpreadingFactor::Sf12) {
+                return Err(Sx1280Error::InvalidSpreadingFactor(self.sf.value()));
+            }
+            if self.bw == Bandwidth::Khz203_125 {
+                return Err(Sx1280Error::InvalidBandwidth(self.bw.khz()));
+            }
+            if self.cr.is_long_interleaved() {
+                return Err(Sx1280Error::InvalidCodingRate(self.cr.param()));
+            }
+        }
+        Ok(())
+    }
+
+    // Longest payload the chip can send or receive. The datasheet limits CR 4/8 with long
+    // interleaving and CRC to 253 bytes.
+    pub fn max_payload_length(&self) -> u8 {
+        if self.crc && self.cr == (CodingRate::Cr4_8 { long_interleave: true }) {
+            253
+        } else {
+            255
+        }
+    }
+
+    // Longest packet the receiver accepts with the packet parameters set up for payload_length
+    // bytes. The payload length in the packet parameters bounds explicit header packets as well.
+    pub fn max_rx_length(&self, payload_length: usize) -> usize {
+        match self.header {
+            LoRaHeader::Explicit => payload_length,
+            LoRaHeader::Implicit(length) => length as usize,
+        }
+    }
+
+    pub fn modulation_params(&self) -> [u8; 3] {
+        [self.sf.param(), self.bw.param(), self.cr.param()]
+    }
+
+    pub fn packet_params(&self, payload_length: u8) -> Result<[u8; 7]> {
+        let (header, length) = match self.header {
+            LoRaHeader::Explicit => (0x00, payload_length),
+            LoRaHeader::Implicit(length) => (0x80, length),
+        };
+        Ok([
+            encode_preamble(self.preamble)?,
+            header,
+            length,
+            if self.crc { 0x20 } else { 0x00 },
+            if self.invert_iq { 0x00 } else { 0x40 },
+            0x00,
+            0x00,
+        ])
+    }
+}
+
+// Encodes a LoRa preamble length of 8 to 61440 symbols as mantissa * 2^exponent, using the next
+// longer preamble if there's no exact match (e.g. any odd length)
+pub fn encode_preamble(preamble_length: u32) -> Result<u8> {
+    if !(8..=61440).contains(&preamble_length) {
+        return Err(Sx1280Error::InvalidPreambleLength(preamble_length));
+    }
+
+    for e in 1..=15u8 {
+        for m in 1..=15u8 {
+            if (m as u32) * (1u32 << e) >= preamble_length {
+                return Ok((e << 4) | m);
+            }
+        }
+    }
+    Err(Sx1280Error::InvalidPreambleLength(preamble_length))
+}
+
+// Inverse of encode_preamble
+pub fn decode_preamble(param: u8) -> u32 {
+    ((param & 0x0F) as u32) * (1u32 << ((param & 0xF0) >> 4))
+}
+
+// SX1280_CMD_SET_CAD_PARAMS, number of symbols a channel activity detection listens for. More
+// symbols detect weaker signals at the cost of a longer CAD.
+pub fn encode_cad_symbols(symbols: u8) -> Result<u8> {
+    match symbols {
+        1 => Ok(0x00),
+        2 => Ok(0x20),
+        4 => Ok(0x40),
+        8 => Ok(0x60),
+        16 => Ok(0x80),
+        _ => Err(Sx1280Error::InvalidCadSymbols(symbols)),
+    }
+}
+
+// Listen-before-talk: a CAD before every transmission attempt, backing off for a random time
+// while the channel is busy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LbtConfig {
+    // CAD length, 1, 2, 4, 8 or 16 symbols
+    pub cad_symbols: u8,
+    // how many CADs to try before giving up
+    pub attempts: u32,
+    // the backoff after a busy channel is picked uniformly from this range
+    pub backoff_min: Duration,
+    pub backoff_max: Duration,
+}
+
+impl Default for LbtConfig {
+    fn default() -> Self {
+        LbtConfig {
+            cad_symbols: 8,
+            attempts: 8,
+            backoff_min: Duration::from_millis(5),
+            backoff_max: Duration::from_millis(50),
+        }
+    }
+}
+
+impl LbtConfig {
+    pub fn validate(&self) -> Result<()> {
+        encode_cad_symbols(self.cad_symbols)?;
+        if self.backoff_min > self.backoff_max {
+            return Err(Sx1280Error::InvalidTimeout(self.backoff_min));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_preamble_exact() {
+        assert_eq!(encode_preamble(8).unwrap(), 0x14);
+        assert_eq!(encode_preamble(12).unwrap(), 0x16);
+        assert_eq!(encode_preamble(30).unwrap(), 0x1F);
+        assert_eq!(encode_preamble(64).unwrap(), 0x38);
+        assert_eq!(encode_preamble(61440).unwrap(), 0xCF);
+    }
+
+    #[test]
+    fn encode_preamble_rounds_up() {
+        assert_eq!(encode_preamble(9).unwrap(), 0x15);
+        assert_eq!(encode_preamble(31).unwrap(), 0x28);
+        assert_eq!(encode_preamble(61).unwrap(), 0x38);
+        assert_eq!(encode_preamble(65).unwrap(), 0x39);
+        assert_eq!(encode_preamble(61439).unwrap(), 0xCF);
+    }
+
+    #[test]
+    fn encode_preamble_out_of_range() {
+        assert!(encode_preamble(0).is_err());
+        assert!(encode_preamble(7).is_err());
+        assert!(encode_preamble(61441).is_err());
+    }
+
+    #[test]
+    fn decode_preamble_round_trip() {
+        assert_eq!(decode_preamble(0x14), 8);
+        assert_eq!(decode_preamble(0xCF), 61440);
+        for length in 8..=61440 {
+            let decoded = decode_preamble(encode_preamble(length).unwrap());
+            assert!(decoded >= length, "{} decoded as {}", length, decoded);
+            assert_eq!(encode_preamble(decoded).unwrap(), encode_preamble(length).unwrap());
+        }
+    }
+
+    #[test]
+    fn long_interleaving_with_crc_limits_payload() {
+        let config = LoRaConfig { cr: CodingRate::Cr4_8 { long_interleave: true }, ..LoRaConfig::default() };
+        assert_eq!(config.max_payload_length(), 253);
+        assert!(LoRaConfig { header: LoRaHeader::Implicit(253), ..config }.validate(false).is_ok());
+        assert!(LoRaConfig { header: LoRaHeader::Implicit(254), ..config }.validate(false).is_err());
+
+        assert_eq!(LoRaConfig { crc: false, ..config }.max_payload_length(), 255);
+        assert_eq!(LoRaConfig { cr: CodingRate::Cr4_8 { long_interleave: false }, ..config }.max_payload_length(), 255);
+    }
+}
@@ -0,0 +1,43 @@
+// Driver for the Semtech SX1280 2.4 GHz transceiver used as the TEL board's HF radio
+mod airtime;
+mod ble;
+mod device;
+mod error;
+mod event;
+mod flrc;
+mod gfsk;
+mod irq;
+mod lora;
+mod packet;
+mod power;
+mod ranging;
+mod status;
+#[allow(clippy::module_inception)]
+mod sx1280;
+
+pub use airtime::{ble_time_on_air, flrc_time_on_air, gfsk_time_on_air, lora_time_on_air};
+pub use ble::{
+    ble_channel_frequency, ble_whitening_seed, BleConfig, BleConnectionState, BleTestPayload, BLE_ADVERTISING_ACCESS_ADDRESS,
+    BLE_ADVERTISING_CHANNELS, BLE_ADVERTISING_CRC_INIT, BLE_MAX_CHANNEL, BLE_MODULATION_PARAMS,
+};
+pub use device::{Command, Field, Register, RegisterDrift, SX1280_registers, MIRRORED_REGISTERS};
+pub use error::{Result, Sx1280Error};
+pub use event::RadioEvent;
+pub use flrc::{
+    FlrcBitRate, FlrcCodingRate, FlrcConfig, FlrcCrc, PacketLength, Shaping, SyncWordMatch, FLRC_MAX_PAYLOAD_LENGTH,
+    FLRC_MIN_PAYLOAD_LENGTH,
+};
+pub use gfsk::{modulation_index, GfskBitRate, GfskConfig, GfskCrc};
+pub use irq::{Dio, DioMapping, Irq};
+pub use lora::{
+    decode_preamble, encode_cad_symbols, encode_preamble, Bandwidth, CodingRate, LbtConfig, LoRaConfig, LoRaHeader,
+    SpreadingFactor,
+};
+pub use packet::{fei_to_hz, FskPacketStatus, PacketStatus, RxPacket, TxReport};
+pub use power::{current_consumption, PaRampTime, RadioMode, RegulatorMode};
+pub use ranging::{
+    ranging_calibration, ranging_distance, AdvancedRangingCapture, RangingConfig, RangingResult, RangingResultType,
+    RangingRole, RssiCorrection,
+};
+pub use status::{CircuitMode, CommandStatus, Status};
+pub use sx1280::*;
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+// Outcome of a completed transmission
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TxReport {
+    // payload bytes sent
+    pub length: usize,
+    // time from SetTx until TxDone was seen, which includes the PA ramp and the host's IRQ latency
+    pub airtime: Duration,
+}
+
+// Reception flags GFSK, FLRC and BLE report in GetPacketStatus
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FskPacketStatus {
+    // RSSI averaged over the sync word, in dBm
+    pub rssi: f32,
+    pub sync_error: bool,
+    pub length_error: bool,
+    pub crc_error: bool,
+    pub abort_error: bool,
+    pub header_received: bool,
+    pub packet_received: bool,
+    // which of sync words 1 to 3 matched, if any
+    pub sync_word: Option<u8>,
+}
+
+impl FskPacketStatus {
+    pub fn from_bytes(raw: [u8; 5]) -> FskPacketStatus {
+        let errors = raw[2];
+        FskPacketStatus {
+            rssi: -(raw[1] as f32) / 2.0,
+            sync_error: errors & 0x40 != 0,
+            length_error: errors & 0x20 != 0,
+            crc_error: errors & 0x10 != 0,
+            abort_error: errors & 0x08 != 0,
+            header_received: errors & 0x04 != 0,
+            packet_received: errors & 0x02 != 0,
+            sync_word: match raw[4] & 0x07 {
+                0 => None,
+                sync => Some(sync),
+            },
+        }
+    }
+}
+
+// Signal quality of a received packet, from GetPacketStatus and, for LoRa, the frequency error
+// indicator
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PacketStatus {
+    LoRa {
+        // RSSI averaged over the preamble, in dBm
+        rssi: f32,
+        // in dB
+        snr: f32,
+        // offset of the received carrier from ours, in Hz
+        frequency_error: f32,
+    },
+    Gfsk(FskPacketStatus),
+    Flrc(FskPacketStatus),
+    Ble(FskPacketStatus),
+}
+
+impl PacketStatus {
+    // fei is the raw contents of Register::FeiByte, bandwidth_khz the LoRa bandwidth it was
+    // measured in
+    pub fn lora(raw: [u8; 5], fei: u32, bandwidth_khz: f32) -> PacketStatus {
+        PacketStatus::LoRa {
+            rssi: -(raw[0] as f32) / 2.0,
+            snr: (raw[1] as i8) as f32 / 4.0,
+            frequency_error: fei_to_hz(fei, bandwidth_khz),
+        }
+    }
+
+    pub fn rssi(&self) -> f32 {
+        match *self {
+            PacketStatus::LoRa { rssi, .. } => rssi,
+            PacketStatus::Gfsk(status) | PacketStatus::Flrc(status) | PacketStatus::Ble(status) => status.rssi,
+        }
+    }
+
+    // Below the noise floor the preamble RSSI mostly measures noise, so the SNR is added back in to
+    // estimate the power of the signal itself
+    pub fn signal_power(&self) -> f32 {
+        match *self {
+            PacketStatus::LoRa { rssi, snr, .. } if snr < 0.0 => rssi + snr,
+            _ => self.rssi(),
+        }
+    }
+}
+
+// Converts the 20-bit two's complement frequency error indicator into Hz
+pub fn fei_to_hz(fei: u32, bandwidth_khz: f32) -> f32 {
+    let signed = (((fei & 0x0F_FFFF) << 12) as i32) >> 12;
+    1.55 * signed as f32 / (1600.0 / bandwidth_khz)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RxPacket {
+    // payload, or the raw PDU in BLE mode
+    pub data: Vec<u8>,
+    pub status: PacketStatus,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_power_applies_datasheet_corrections() {
+        // RSSI is -raw / 2, and a negative LoRa SNR is added on top
+        let weak = PacketStatus::lora([180, (-20i8) as u8, 0, 0, 0], 0, 812.5);
+        assert_eq!(weak.rssi(), -90.0);
+        assert_eq!(weak.signal_power(), -95.0);
+
+        let strong = PacketStatus::lora([180, 20, 0, 0, 0], 0, 812.5);
+        assert_eq!(strong.signal_power(), -90.0);
+
+        let fsk = PacketStatus::Flrc(FskPacketStatus::from_bytes([0, 100, 0, 0, 0]));
+        assert_eq!(fsk.signal_power(), -50.0);
+    }
+
+    #[test]
+    fn fei_to_hz_scales_with_bandwidth() {
+        assert_eq!(fei_to_hz(1000, 1600.0), 1550.0);
+        assert_eq!(fei_to_hz(1000, 800.0), 775.0);
+        assert!((fei_to_hz(1000, 203.125) - 196.777).abs() < 0.001);
+    }
+
+    #[test]
+    fn fei_to_hz_sign_extends_20_bits() {
+        assert_eq!(fei_to_hz(0x0F_FC18, 1600.0), -1550.0);
+        assert_eq!(fei_to_hz(0x08_0000, 1600.0), -0x8_0000 as f32 * 1.55);
+        assert_eq!(fei_to_hz(0x07_FFFF, 1600.0), 0x7_FFFF as f32 * 1.55);
+        // bits above the 20-bit field are ignored
+        assert_eq!(fei_to_hz(0xF0_03E8, 1600.0), 1550.0);
+    }
+}
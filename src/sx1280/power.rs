@@ -0,0 +1,135 @@
+use std::ops::RangeInclusive;
+
+// SX1280_CMD_SET_REGULATOR_MODE. DC-DC needs the external inductor fitted; it is only used in
+// STDBY_XOSC, FS, RX and TX, the LDO supplies sleep and STDBY_RC either way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegulatorMode {
+    Ldo = 0x00,
+    DcDc = 0x01,
+}
+
+// SX1280_CMD_SET_TX_PARAMS, how long the PA takes to ramp up to the output power
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaRampTime {
+    Us2,
+    Us4,
+    Us6,
+    Us8,
+    Us10,
+    Us12,
+    Us16,
+    Us20,
+}
+
+impl PaRampTime {
+    pub fn param(&self) -> u8 {
+        match *self {
+            PaRampTime::Us2 => 0x00,
+            PaRampTime::Us4 => 0x20,
+            PaRampTime::Us6 => 0x40,
+            PaRampTime::Us8 => 0x60,
+            PaRampTime::Us10 => 0x80,
+            PaRampTime::Us12 => 0xA0,
+            PaRampTime::Us16 => 0xC0,
+            PaRampTime::Us20 => 0xE0,
+        }
+    }
+
+    pub fn micros(&self) -> u32 {
+        match *self {
+            PaRampTime::Us2 => 2,
+            PaRampTime::Us4 => 4,
+            PaRampTime::Us6 => 6,
+            PaRampTime::Us8 => 8,
+            PaRampTime::Us10 => 10,
+            PaRampTime::Us12 => 12,
+            PaRampTime::Us16 => 16,
+            PaRampTime::Us20 => 20,
+        }
+    }
+}
+
+// Operating modes with a distinct supply current
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RadioMode {
+    // retain is whether the data RAM or buffer is kept through sleep
+    Sleep { retain: bool },
+    StandbyRc,
+    StandbyXosc,
+    Fs,
+    Rx,
+    // transmitting at the given output power in dBm
+    Tx { power: i8 },
+}
+
+// Typical TX current in mA with DC-DC over output power, as (dBm, mA) - SX1280 datasheet v3.0. The
+// highest power setting of +13 dBm is specified as 12.5 dBm. Powers in between are linearly
+// interpolated, which is an estimate rather than a datasheet figure.
+const TX_CURRENT: [(f32, f32); 3] = [(0.0, 10.0), (10.0, 18.0), (12.5, 24.0)];
+
+// With the LDO the modes that use the regulator draw about twice the DC-DC current - SX1280
+// datasheet v3.0 section 14.7.1
+const LDO_FACTOR: f32 = 2.0;
+
+// Typical supply current range in mA for a mode - SX1280 datasheet v3.0. Only an estimate for
+// power budgets; the actual draw depends on supply voltage and temperature. RX is the LoRa figure;
+// FLRC and GFSK reach up to 8.6 mA. TX below 0 dBm is not covered and gives None.
+pub fn current_consumption(mode: RadioMode, regulator: RegulatorMode) -> Option<RangeInclusive<f32>> {
+    let typical = |ma: f32| Some(ma..=ma);
+    let dc_dc = match mode {
+        // 0.2 uA without retention up to 1.2 uA keeping data RAM and buffer
+        RadioMode::Sleep { retain: false } => return typical(0.000_2),
+        RadioMode::Sleep { retain: true } => return Some(0.000_2..=0.001_2),
+        RadioMode::StandbyRc => return typical(0.7),
+        RadioMode::StandbyXosc => 1.0..=1.0,
+        RadioMode::Fs => 2.8..=2.8,
+        // from the narrowest bandwidth in low power mode up to the widest in high sensitivity mode
+        RadioMode::Rx => 5.5..=8.2,
+        RadioMode::Tx { power } => {
+            let power = (power as f32).min(TX_CURRENT[TX_CURRENT.len() - 1].0);
+            if power < TX_CURRENT[0].0 {
+                return None;
+            }
+            let upper = TX_CURRENT.iter().position(|point| point.0 >= power)?.max(1);
+            let (low, high) = (TX_CURRENT[upper - 1], TX_CURRENT[upper]);
+            let ma = low.1 + (high.1 - low.1) * (power - low.0) / (high.0 - low.0);
+            ma..=ma
+        }
+    };
+    match regulator {
+        RegulatorMode::DcDc => Some(dc_dc),
+        RegulatorMode::Ldo => Some(dc_dc.start() * LDO_FACTOR..=dc_dc.end() * LDO_FACTOR),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(power: i8, regulator: RegulatorMode) -> Option<RangeInclusive<f32>> {
+        current_consumption(RadioMode::Tx { power }, regulator)
+    }
+
+    #[test]
+    fn tx_datasheet_points() {
+        assert_eq!(tx(0, RegulatorMode::DcDc), Some(10.0..=10.0));
+        assert_eq!(tx(10, RegulatorMode::DcDc), Some(18.0..=18.0));
+        assert_eq!(tx(13, RegulatorMode::DcDc), Some(24.0..=24.0));
+        assert_eq!(tx(5, RegulatorMode::DcDc), Some(14.0..=14.0));
+        assert_eq!(tx(-1, RegulatorMode::DcDc), None);
+    }
+
+    #[test]
+    fn ldo_draws_twice_dc_dc() {
+        assert_eq!(tx(10, RegulatorMode::Ldo), Some(36.0..=36.0));
+        assert_eq!(current_consumption(RadioMode::Rx, RegulatorMode::Ldo), Some(11.0..=16.4));
+        assert_eq!(current_consumption(RadioMode::Fs, RegulatorMode::Ldo), Some(5.6..=5.6));
+    }
+
+    #[test]
+    fn regulator_unused_in_sleep_and_standby_rc() {
+        for mode in [RadioMode::Sleep { retain: false }, RadioMode::Sleep { retain: true }, RadioMode::StandbyRc] {
+            assert_eq!(current_consumption(mode, RegulatorMode::Ldo), current_consumption(mode, RegulatorMode::DcDc));
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use super::error::{Result, Sx1280Error};
+use super::lora::{Bandwidth, LoRaConfig, SpreadingFactor};
+
+// SX1280_CMD_SET_RANGING_ROLE
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangingRole {
+    Slave = 0x00,
+    Master = 0x01,
+}
+
+// Which of the chip's result pipelines Register::RangingResult shows, via Field::RANGING_RESULT_MUX
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangingResultType {
+    Raw = 0x00,
+    Averaged = 0x01,
+    Debiased = 0x02,
+    Filtered = 0x03,
+}
+
+// Per-SF/BW round trip delay calibration for SF5 to SF10, as RNG_CALIB_0400/0800/1600 in Semtech's
+// SX1280 reference driver
+const CALIBRATION_BW400: [u16; 6] = [10299, 10271, 10244, 10242, 10230, 10246];
+const CALIBRATION_BW800: [u16; 6] = [11486, 11474, 11453, 11426, 11417, 11401];
+const CALIBRATION_BW1600: [u16; 6] = [13308, 13493, 13528, 13515, 13430, 13376];
+
+// Calibration value for Register::RangingCalibration
+pub fn ranging_calibration(sf: SpreadingFactor, bw: Bandwidth) -> Result<u16> {
+    let table = match bw {
+        Bandwidth::Khz406_25 => &CALIBRATION_BW400,
+        Bandwidth::Khz812_5 => &CALIBRATION_BW800,
+        Bandwidth::Khz1625 => &CALIBRATION_BW1600,
+        Bandwidth::Khz203_125 => return Err(Sx1280Error::InvalidBandwidth(bw.khz())),
+    };
+    match sf.value() {
+        5..=10 => Ok(table[(sf.value() - 5) as usize]),
+        other => Err(Sx1280Error::InvalidSpreadingFactor(other)),
+    }
+}
+
+// Converts the 24-bit result register into metres. Raw results are a signed round trip time in
+// units of 1 / (2^12 * BW); the processed ones are already distances in steps of 0.2 m.
+pub fn ranging_distance(raw: u32, result_type: RangingResultType, bw: Bandwidth) -> f32 {
+    match result_type {
+        RangingResultType::Raw => {
+            let signed = ((raw << 8) as i32) >> 8;
+            (signed as f32) * 150.0 / (4096.0 * bw.khz() / 1000.0)
+        }
+        _ => (raw & 0xFF_FFFF) as f32 * 0.2,
+    }
+}
+
+// Linear correction for the range bias that grows as the signal gets weaker, on top of the
+// datasheet's fixed RSSI corrections that go into the signal power. There are no generic
+// coefficients: they come from measuring known distances on the actual hardware.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RssiCorrection {
+    // signal power in dBm at which the measured distance needs no correction
+    pub reference_dbm: f32,
+    // metres to subtract for every dB the signal power falls below the reference
+    pub metres_per_db: f32,
+}
+
+impl RssiCorrection {
+    pub fn apply(&self, distance: f32, signal_power: f32) -> f32 {
+        distance - self.metres_per_db * (self.reference_dbm - signal_power)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RangingConfig {
+    // modulation and packet parameters of the exchange; ranging only runs at SF5-10, 400 kHz and up
+    pub lora: LoRaConfig,
+    // the slave's address: a master sends requests to it, a slave only answers requests for it,
+    // and an advanced ranging listener only captures requests for it
+    pub address: u32,
+    // how many bits of the address a slave compares, 8 to 32 in steps of 8
+    pub id_check_bits: u8,
+    pub result_type: RangingResultType,
+    // number of results the filtered result is averaged over, 8 to 255
+    pub filter_window: u8,
+    // overrides the reference calibration for this SF/BW
+    pub calibration: Option<u16>,
+    // extra correction of the distance for the signal power; None applies only the datasheet's
+    // fixed RSSI corrections
+    pub rssi_correction: Option<RssiCorrection>,
+}
+
+impl Default for RangingConfig {
+    fn default() -> Self {
+        RangingConfig {
+            lora: LoRaConfig::default(),
+            address: 0x0000_0000,
+            id_check_bits: 32,
+            result_type: RangingResultType::Filtered,
+            filter_window: 8,
+            calibration: None,
+            rssi_correction: None,
+        }
+    }
+}
+
+impl RangingConfig {
+    pub fn validate(&self) -> Result<()> {
+        self.lora.validate(true)?;
+        if !matches!(self.id_check_bits, 8 | 16 | 24 | 32) {
+            return Err(Sx1280Error::InvalidRangingConfiguration(self.id_check_bits));
+        }
+        if self.filter_window < 8 {
+            return Err(Sx1280Error::InvalidRangingConfiguration(self.filter_window));
+        }
+        Ok(())
+    }
+
+    pub fn calibration(&self) -> Result<u16> {
+        match self.calibration {
+            Some(calibration) => Ok(calibration),
+            None => ranging_calibration(self.lora.sf, self.lora.bw),
+        }
+    }
+
+    // Value for Field::RANGING_ID_CHECK_LENGTH
+    pub fn id_check_length(&self) -> u8 {
+        (self.id_check_bits / 8) - 1
+    }
+}
+
+// Outcome of a completed master exchange
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RangingResult {
+    pub result_type: RangingResultType,
+    // contents of Register::RangingResult
+    pub raw: u32,
+    // contents of Register::RangingRssi: dB above a threshold below the noise floor, so higher
+    // is stronger - SX1280 datasheet v3.0 section 14.5.3
+    pub rssi: u8,
+    // power of the slave's response in dBm, from GetPacketStatus with the datasheet's RSSI / 2
+    // scaling and the SNR added back below the noise floor, see PacketStatus::signal_power
+    pub signal_power: f32,
+    // calibrated and RSSI-corrected distance to the slave
+    pub distance: f32,
+}
+
+// A ranging request overheard in advanced ranging mode, for time-difference processing across
+// several passive listeners
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdvancedRangingCapture {
+    // address the master was ranging against
+    pub address: u32,
+    // raw timing result, in the same units as RangingResultType::Raw
+    pub raw: u32,
+    // ranging RSSI and signal power of the request, as in RangingResult
+    pub rssi: u8,
+    pub signal_power: f32,
+}
+
+impl AdvancedRangingCapture {
+    // The raw result is a 24-bit two's complement number
+    pub fn raw_signed(&self) -> i32 {
+        ((self.raw << 8) as i32) >> 8
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+
+// Circuit mode reported in bits 7:5 of the status byte
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitMode {
+    StdbyRc,
+    StdbyXosc,
+    Fs,
+    Rx,
+    Tx,
+    Unknown(u8),
+}
+
+// Outcome of the previous command, reported in bits 4:2 of the status byte
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandStatus {
+    Success,
+    DataAvailable,
+    Timeout,
+    ProcessingError,
+    ExecFailure,
+    TxDone,
+    Unknown(u8),
+}
+
+// The status byte the SX1280 clocks out on MISO while the host sends an opcode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Status {
+    pub mode: CircuitMode,
+    pub command: CommandStatus,
+    raw: u8,
+}
+
+impl Status {
+    pub fn from_byte(raw: u8) -> Status {
+        let mode = match (raw >> 5) & 0x07 {
+            0x2 => CircuitMode::StdbyRc,
+            0x3 => CircuitMode::StdbyXosc,
+            0x4 => CircuitMode::Fs,
+            0x5 => CircuitMode::Rx,
+            0x6 => CircuitMode::Tx,
+            other => CircuitMode::Unknown(other),
+        };
+        let command = match (raw >> 2) & 0x07 {
+            0x1 => CommandStatus::Success,
+            0x2 => CommandStatus::DataAvailable,
+            0x3 => CommandStatus::Timeout,
+            0x4 => CommandStatus::ProcessingError,
+            0x5 => CommandStatus::ExecFailure,
+            0x6 => CommandStatus::TxDone,
+            other => CommandStatus::Unknown(other),
+        };
+        Status { mode, command, raw }
+    }
+
+    pub fn raw(&self) -> u8 {
+        self.raw
+    }
+
+    // The chip could not decode the last command, or decoded it but could not carry it out
+    pub fn is_error(&self) -> bool {
+        matches!(self.command, CommandStatus::ProcessingError | CommandStatus::ExecFailure)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}/{:?} ({:#04x})", self.mode, self.command, self.raw)
+    }
+}
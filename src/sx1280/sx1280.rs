@@ -1,521 +1,1916 @@
-use libm::{fabs};
-
-// error codes
-const ERR_INVALID_BANDWIDTH: i16   = -8;
-const ERR_INVALID_SPREADING_FACTOR = -9
-const ERR_INVALID_CODING_RATE      = -10
-const ERR_INVALID_BIT_RANGE        = -11
-const ERR_INVALID_FREQUENCY        = -12
-const ERR_INVALID_OUTPUT_POWER     = -13
-const ERR_INVALID_PREAMBLE_LENGTH  = -18
-const ERR_WRONG_MODEM: i16         = -20;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::RangeInclusive;
+use std::sync::mpsc::Sender;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use spidev::{Spidev, SpidevTransfer};
+
+use crate::gpio;
+use super::airtime::{ble_time_on_air, flrc_time_on_air, gfsk_time_on_air, lora_time_on_air};
+use super::ble::{ble_channel_frequency, ble_whitening_seed, BleConfig, BLE_ADVERTISING_CHANNELS, BLE_MODULATION_PARAMS};
+use super::device::{Command, Field, Register, RegisterDrift, SX1280_registers, MIRRORED_REGISTERS};
+use super::error::{Result, Sx1280Error};
+use super::event::RadioEvent;
+use super::flrc::{
+    FlrcBitRate, FlrcCodingRate, FlrcConfig, FlrcCrc, PacketLength, Shaping, SyncWordMatch, FLRC_MAX_PAYLOAD_LENGTH,
+    FLRC_MIN_PAYLOAD_LENGTH,
+};
+use super::gfsk::{GfskBitRate, GfskConfig, GfskCrc};
+use super::irq::{Dio, DioMapping, Irq};
+use super::lora::{encode_cad_symbols, Bandwidth, CodingRate, LbtConfig, LoRaConfig, LoRaHeader, SpreadingFactor};
+use super::packet::{FskPacketStatus, PacketStatus, RxPacket, TxReport};
+use super::power::{current_consumption, PaRampTime, RadioMode, RegulatorMode};
+use super::ranging::{ranging_distance, AdvancedRangingCapture, RangingConfig, RangingResult, RangingResultType, RangingRole};
+use super::status::Status;
 
 // SX1280 physical layer properties
-const SX1280_FREQUENCY_STEP_SIZE = 198.3642578;
-const SX1280_MAX_PACKET_LENGTH = 255;
-const SX1280_CRYSTAL_FREQ = 52.0;
-const SX1280_DIV_EXPONENT = 18;
+pub const SX1280_FREQUENCY_STEP_SIZE: f64 = 198.3642578;
+pub const SX1280_MAX_PACKET_LENGTH: u8 = 255;
+pub const SX1280_BUFFER_SIZE: usize = 256;
+pub const SX1280_CRYSTAL_FREQ: f64 = 52.0;
+pub const SX1280_DIV_EXPONENT: u32 = 18;
+pub const SX1280_DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_millis(20);
+pub const SX1280_RESET_TIMEOUT: Duration = Duration::from_millis(100);
 
 // PacketType Definition
-const PACKET_TYPE_GFSK    = 0x00
-const PACKET_TYPE_LORA    = 0x01
-const PACKET_TYPE_RANGING = 0x02
-const PACKET_TYPE_FLRC    = 0x03
-const PACKET_TYPE_BLE     = 0x04
-
-// SX1280_CMD_SET_MODULATION_PARAMS
-const SX128X_LORA_SF_5  = 0x50                          //  7     0   LoRa spreading factor: 5
-const SX128X_LORA_SF_6  = 0x60                          //  7     0                          6
-const SX128X_LORA_SF_7  = 0x70                          //  7     0                          7
-const SX128X_LORA_SF_8  = 0x80                          //  7     0                          8
-const SX128X_LORA_SF_9  = 0x90                          //  7     0                          9
-const SX128X_LORA_SF_10 = 0xA0                          //  7     0                          10
-const SX128X_LORA_SF_12 = 0xC0                          //  7     0                          12
-const SX128X_LORA_SF_11 = 0xB0                          //  7     0                          11
-const SX128X_LORA_BW_1625_00 = 0x0A                     //  7     0   LoRa bandwidth: 1625.0 kHz
-const SX128X_LORA_BW_812_50  = 0x18                     //  7     0                   812.5 kHz
-const SX128X_LORA_BW_406_25  = 0x26                     //  7     0                   406.25 kHz
-const SX128X_LORA_BW_203_125 = 0x34                     //  7     0                   203.125 kHz
-
-bandwidth: i16,
-bandwidthkhz: f32,
-spreading_factor: u8,
-coding_rate_lora: u8,
-coding_rate_flrc: u8,
-preamble_length_lora,
-bit_rate,
-shaping,
-power: i8,
-    
-fn CheckRange(var: f32, min: f32, max: f32, err: i16) {
-    if !(var >= min && var <= max) {
-        return err
-    }
-}
+pub const PACKET_TYPE_GFSK: u8    = 0x00;
+pub const PACKET_TYPE_LORA: u8    = 0x01;
+pub const PACKET_TYPE_RANGING: u8 = 0x02;
+pub const PACKET_TYPE_FLRC: u8    = 0x03;
+pub const PACKET_TYPE_BLE: u8     = 0x04;
 
-fn Assert(state_var) {
-  if state_var != ERR_NONE {
-    return state_var;
-  }
-}
+// SX1280_CMD_SET_TX/SX1280_CMD_SET_RX/SX1280_CMD_SET_RX_DUTY_CYCLE step, indexed by the period base parameter
+const PERIOD_BASE_STEPS: [Duration; 4] = [
+    Duration::from_nanos(15_625),
+    Duration::from_nanos(62_500),
+    Duration::from_millis(1),
+    Duration::from_millis(4),
+];
+
+// Pause between reads of BUSY, so a long wait doesn't keep a core spinning on sysfs
+const BUSY_POLL_INTERVAL: Duration = Duration::from_micros(20);
+
+// How long the host keeps polling after the chip's own timeout should have fired
+const IRQ_POLL_MARGIN: Duration = Duration::from_millis(100);
+
+// IRQs that end a receive
+const RX_EVENTS: Irq = Irq::RX_DONE.union(Irq::HEADER_ERROR).union(Irq::CRC_ERROR).union(Irq::RX_TX_TIMEOUT);
 
-fn SetFrequency(freq: f32) -> i16 {
-    CheckRange(freq, 2400.0, 2500.0, ERR_INVALID_FREQUENCY);
+// Slack added to the estimated time on air before the chip aborts a transmission
+const TX_TIMEOUT_MARGIN: Duration = Duration::from_millis(10);
 
-    // calculate raw value
-    let frf: u32 = (freq * ((1 as u32) << SX1280_DIV_EXPONENT)) / SX1280_CRYSTAL_FREQ;
+// SX1280_CMD_SET_AUTO_TX counts from RxDone, but the chip needs this long to switch to TX on top
+const AUTO_TX_OFFSET: Duration = Duration::from_micros(33);
 
-    return SetRfFrequency(frf);
+// SX1280_CMD_SET_SLEEP
+const SLEEP_RETAIN_DATA_RAM: u8 = 0x01;                 //  0     0   keep the saved context in data RAM
+const SLEEP_RETAIN_DATA_BUFFER: u8 = 0x02;              //  1     1   keep the packet data buffer
+
+// SX1280_CMD_SET_STANDBY
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StandbyMode {
+    Rc = 0x00,
+    Xosc = 0x01,
 }
 
-fn SetBandwidth(bw: f32) -> i16 {
-    // check active modem
-    let modem: u8 = GetPacketType();
-
-    if modem == PACKET_TYPE_LORA {
-        // check range for LoRa
-        CheckRange(bw, 203.125, 1625.0, ERR_INVALID_BANDWIDTH);
-    } else if modem == PACKET_TYPE_RANGING {
-        // check range for ranging
-        CheckRange(bw, 406.25, 1625.0, ERR_INVALID_BANDWIDTH);
-    } else {
-        return ERR_WRONG_MODEM;
-    }
-  
-    if fabs(bw - 203.125) <= 0.001 {
-        self::bandwidth = SX128X_LORA_BW_203_125;
-    } else if fabs(bw - 406.25) <= 0.001 {
-        self::bandwidth = SX128X_LORA_BW_406_25;
-    } else if fabs(bw - 812.5) <= 0.001 {
-        self::bandwidth = SX128X_LORA_BW_812_50;
-    } else if fabs(bw - 1625.0) <= 0.001 {
-        self::bandwidth = SX128X_LORA_BW_1625_00;
-    } else {
-        return ERR_INVALID_BANDWIDTH;
-    }
-  
-    // update modulation parameters
-    self::bandwidthkhz = bw;
-    return SetModulationParams(self::spreading_factor, self::bandwidth, self::coding_rate_lora);
+// How the chip came back from sleep, see SX1280::wake
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WakeUp {
+    // the retained context was intact and nothing had to be configured again
+    Warm,
+    // the chip was initialised from scratch and the cached configuration applied again
+    Cold,
 }
 
-fn SetSpreadingFactor(sf: u8) -> i16 {
-    // check active modem
-    let modem: u8 = GetPacketType();
-    if modem == PACKET_TYPE_LORA {
-        // check range for LoRa
-        CheckRange(sf, 5, 12, ERR_INVALID_SPREADING_FACTOR);
-    } else if modem == PACKET_TYPE_RANGING {
-        // check range for ranging
-        CheckRange(sf, 5, 10, ERR_INVALID_SPREADING_FACTOR);
-    } else {
-      return ERR_WRONG_MODEM;
-    }
-  
-    // update modulation parameters
-    self::spreading_factor = sf << 4;
-    let state: i16 = SetModulationParams(self::spreading_factor, self::bandwidth, self::coding_rate_lora);
-    Assert(state);
-  
-    // update mystery register in LoRa mode - SX1280 datasheet v3.0 section 13.4.1
-    if modem == X128X_PACKET_TYPE_LORA {
-      let data: u8 = 0;
-      if (self::spreading_factor == SX128X_LORA_SF_5) || (self::spreading_factor == SX128X_LORA_SF_6) {
-        data = 0x1E;
-      } else if (self::spreading_factor == SX128X_LORA_SF_7) || (self::spreading_factor == SX128X_LORA_SF_8) {
-        data = 0x37;
-      } else {
-        data = 0x32;
-      }
-      const SX128X_REG_LORA_SF_CONFIG = 0x0925;
-      state = WriteRegister(SX128X_REG_LORA_SF_CONFIG, &[data], 1);
-    }
-  
-    return state;
+pub struct SX1280 {
+    spi: Spidev,
+    cs: &'static str,
+    busy: &'static str,
+    nreset: &'static str,
+    power_enable: Option<&'static str>,
+    irq_pin: Option<(Dio, &'static str)>,
+    dio_mapping: DioMapping,
+    regs: SX1280_registers,
+    last_status: Option<Status>,
+    busy_timeout: Duration,
+    tx_base: u8,
+    rx_base: u8,
+    // whether the chip is asleep, and if so whether it kept its configuration
+    sleeping: Option<bool>,
+    // shadow copy as it was when the chip went to sleep, written back if the chip lost it
+    sleep_regs: Option<SX1280_registers>,
+
+    packet_type: u8,
+    regulator_mode: RegulatorMode,
+    lora: LoRaConfig,
+    flrc: FlrcConfig,
+    gfsk: GfskConfig,
+    ble: BleConfig,
+    ranging: RangingConfig,
+    frf: Option<u32>,
+    power: u8,
+    pa_ramp: PaRampTime,
+    payload_length: u8,
 }
-  
-fn SetCodingRate(cr: u8, long_inter_leaving: bool) -> i16 {
-    // check active modem
-    let modem: u8 = GetPacketType();
-  
-    // LoRa/ranging
-    if (modem == PACKET_TYPE_LORA) || (modem == PACKET_TYPE_RANGING) {
-        CheckRange(cr, 5, 8, ERR_INVALID_CODING_RATE);
-    
-        // update modulation parameters
-        if long_inter_leaving && (modem == PACKET_TYPE_LORA) {
-            self::coding_rate_lora = cr;
+
+impl SX1280 {
+    /// Takes ownership of the SPI bus and the chip-select, BUSY and NRESET GPIOs of one SX1280.
+    pub fn new(spi: Spidev, cs: &'static str, busy: &'static str, nreset: &'static str) -> SX1280 {
+        gpio::set_output(cs);
+        gpio::set_high(cs);
+        gpio::set_input(busy);
+        gpio::set_output(nreset);
+        gpio::set_high(nreset);
+
+        SX1280 {
+            spi,
+            cs,
+            busy,
+            nreset,
+            power_enable: None,
+            irq_pin: None,
+            dio_mapping: DioMapping::default(),
+            regs: SX1280_registers::default(),
+            last_status: None,
+            busy_timeout: SX1280_DEFAULT_BUSY_TIMEOUT,
+            tx_base: 0x00,
+            rx_base: 0x00,
+            sleeping: None,
+            sleep_regs: None,
+
+            packet_type: PACKET_TYPE_LORA,
+            regulator_mode: RegulatorMode::Ldo,
+            lora: LoRaConfig::default(),
+            flrc: FlrcConfig::default(),
+            gfsk: GfskConfig::default(),
+            ble: BleConfig::default(),
+            ranging: RangingConfig::default(),
+            frf: None,
+            power: 28,
+            pa_ramp: PaRampTime::Us10,
+            payload_length: SX1280_MAX_PACKET_LENGTH,
+        }
+    }
+
+    /// Hands the driver the GPIO that switches the radio's supply rail, so reset() can power it up.
+    pub fn set_power_pin(&mut self, pin: &'static str) {
+        gpio::set_output(pin);
+        self.power_enable = Some(pin);
+    }
+
+    /// Hands the driver the GPIO wired to one of the DIO pins. The IRQs each operation waits for are
+    /// then routed to that DIO, and the driver sleeps on the pin's rising edge instead of polling
+    /// GetIrqStatus over SPI.
+    pub fn set_irq_pin(&mut self, dio: Dio, pin: &'static str) {
+        gpio::set_input(pin);
+        gpio::set_edge(pin, "rising");
+        self.irq_pin = Some((dio, pin));
+    }
+
+    /// Routes additional IRQs to DIO pins, e.g. to drive an external PA or LED from hardware. These
+    /// stay enabled alongside whatever the current operation waits for.
+    pub fn set_dio_mapping(&mut self, mapping: DioMapping) {
+        self.dio_mapping = mapping;
+    }
+
+    /// Powers the chip, pulses NRESET and waits for it to finish booting.
+    pub fn reset(&mut self) -> Result<()> {
+        if let Some(pin) = self.power_enable {
+            gpio::set_high(pin);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        gpio::set_low(self.nreset);
+        thread::sleep(Duration::from_millis(1));
+        gpio::set_high(self.nreset);
+        self.sleeping = None;
+        self.sleep_regs = None;
+
+        self.wait_busy_for(SX1280_RESET_TIMEOUT)
+    }
+
+    /// Full bring-up: reset, then standby, packet type, regulator and buffer layout, and finally a
+    /// firmware version read to make sure something is actually answering on the bus.
+    pub fn init(&mut self) -> Result<()> {
+        self.reset()?;
+        self.set_standby(StandbyMode::Rc)?;
+        self.set_packet_type(self.packet_type)?;
+        self.set_regulator_mode(self.regulator_mode)?;
+        self.set_buffer_base_address(0x00, 0x00)?;
+
+        let version = self.read_register_value(Register::FirmwareVersion)? as u16;
+        if version == 0x0000 || version == 0xFFFF {
+            return Err(Sx1280Error::ChipNotFound(version));
+        }
+
+        self.load_registers()
+    }
+
+    pub fn registers(&self) -> &SX1280_registers {
+        &self.regs
+    }
+
+    /// Most recent status byte read back from the chip.
+    pub fn last_status(&self) -> Option<Status> {
+        self.last_status
+    }
+
+    /// Sets how long a command may wait for BUSY to drop before giving up with BusyTimeout.
+    pub fn set_busy_timeout(&mut self, timeout: Duration) {
+        self.busy_timeout = timeout;
+    }
+
+    /// Blocks until the chip lowers BUSY, i.e. it is ready to accept the next command.
+    pub fn wait_busy(&self) -> Result<()> {
+        self.wait_busy_for(self.busy_timeout)
+    }
+
+    fn wait_busy_for(&self, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        while gpio::is_high(self.busy) {
+            if start.elapsed() > timeout {
+                return Err(Sx1280Error::BusyTimeout(timeout));
+            }
+            thread::sleep(BUSY_POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    // Clocks tx_buf out while chip select is held low, filling rx_buf with what the chip sent back.
+    // The first byte back is the status latched before this command ran, so it says nothing about
+    // the command itself; callers check a later status byte with check_status.
+    fn transfer(&mut self, tx_buf: &[u8], rx_buf: &mut [u8]) -> Result<()> {
+        self.wait_busy()?;
+
+        gpio::set_low(self.cs);
+        let result = {
+            let mut transfer = SpidevTransfer::read_write(tx_buf, rx_buf);
+            self.spi.transfer(&mut transfer)
+        };
+        gpio::set_high(self.cs);
+        result?;
+
+        self.last_status = Some(Status::from_byte(rx_buf[0]));
+        Ok(())
+    }
+
+    // Decodes a status byte clocked out after cmd executed, failing if the chip rejected cmd
+    fn check_status(&mut self, cmd: Command, byte: u8) -> Result<Status> {
+        let status = Status::from_byte(byte);
+        self.last_status = Some(status);
+        if status.is_error() {
+            return Err(Sx1280Error::CommandStatus(cmd, status));
+        }
+        Ok(status)
+    }
+
+    /// Sends a command followed by its parameters, then a GetStatus to find out whether the chip
+    /// carried it out. SetSleep is not checked, since the GetStatus would wake the chip again.
+    pub fn write_command(&mut self, cmd: Command, params: &[u8]) -> Result<()> {
+        let mut tx_buf = Vec::with_capacity(params.len() + 1);
+        tx_buf.push(cmd.opcode());
+        tx_buf.extend_from_slice(params);
+        let mut rx_buf = vec![0; tx_buf.len()];
+        self.transfer(&tx_buf, &mut rx_buf)?;
+        if cmd == Command::SetSleep {
+            return Ok(());
+        }
+
+        let status = self.get_status()?;
+        self.check_status(cmd, status.raw())?;
+        self.refresh_registers(cmd.rewritten_registers())
+    }
+
+    // Re-reads registers the chip changed on its own so the shadow copy doesn't report them as drift
+    fn refresh_registers(&mut self, regs: &[Register]) -> Result<()> {
+        for &reg in regs {
+            let value = self.read_register_value(reg)?;
+            self.regs.set(reg, value);
+        }
+        Ok(())
+    }
+
+    /// Reads the status byte, whose command field reports on the command sent before it.
+    pub fn get_status(&mut self) -> Result<Status> {
+        let mut rx_buf = [0u8];
+        self.transfer(&[Command::GetStatus.opcode()], &mut rx_buf)?;
+        Ok(Status::from_byte(rx_buf[0]))
+    }
+
+    /// Sends a command and its parameters, then clocks out a status NOP and reads data.len() bytes.
+    /// The status returned during the NOP reports on this command.
+    pub fn read_command(&mut self, cmd: Command, params: &[u8], data: &mut [u8]) -> Result<()> {
+        let offset = params.len() + 2;
+        let mut tx_buf = vec![0; offset + data.len()];
+        tx_buf[0] = cmd.opcode();
+        tx_buf[1..=params.len()].copy_from_slice(params);
+        let mut rx_buf = vec![0; tx_buf.len()];
+
+        self.transfer(&tx_buf, &mut rx_buf)?;
+        self.check_status(cmd, rx_buf[offset - 1])?;
+        data.copy_from_slice(&rx_buf[offset..]);
+        Ok(())
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: &[u8]) -> Result<()> {
+        let mut params = vec![(addr >> 8) as u8, (addr & 0xFF) as u8];
+        params.extend_from_slice(data);
+        self.write_command(Command::WriteRegister, &params)?;
+        self.regs.update(addr, data);
+        Ok(())
+    }
+
+    pub fn read_register(&mut self, addr: u16, data: &mut [u8]) -> Result<()> {
+        self.read_command(Command::ReadRegister, &[(addr >> 8) as u8, (addr & 0xFF) as u8], data)
+    }
+
+    /// Reads a whole named register, most significant byte first. Registers the radio updates on
+    /// its own, like the FEI, are copied into the shadow as they are read.
+    pub fn read_register_value(&mut self, reg: Register) -> Result<u64> {
+        let mut data = vec![0; reg.width()];
+        self.read_register(reg.address(), &mut data)?;
+        let value = reg.decode(&data);
+        if reg.is_volatile() {
+            self.regs.set(reg, value);
+        }
+        Ok(value)
+    }
+
+    pub fn write_register_value(&mut self, reg: Register, value: u64) -> Result<()> {
+        self.write_register(reg.address(), &reg.encode(value))
+    }
+
+    pub fn read_field(&mut self, field: Field) -> Result<u8> {
+        let byte = self.read_register_value(field.register)? as u8;
+        Ok(field.extract(byte))
+    }
+
+    /// Read-modify-write of a single field, leaving the other bits of the register untouched.
+    pub fn write_field(&mut self, field: Field, value: u8) -> Result<()> {
+        let byte = self.read_register_value(field.register)? as u8;
+        self.write_register_value(field.register, field.insert(byte, value) as u64)
+    }
+
+    /// Reloads the shadow copy of every mirrored register from the chip.
+    pub fn load_registers(&mut self) -> Result<()> {
+        for reg in MIRRORED_REGISTERS {
+            let value = self.read_register_value(reg)?;
+            self.regs.set(reg, value);
+        }
+        Ok(())
+    }
+
+    /// Re-reads the configuration registers and reports every one that no longer matches the
+    /// shadow copy, e.g. because the chip browned out or was reset behind our back.
+    pub fn verify(&mut self) -> Result<Vec<RegisterDrift>> {
+        let mut drift = Vec::new();
+        for reg in MIRRORED_REGISTERS.into_iter().filter(|reg| !reg.is_volatile()) {
+            let expected = self.regs.get(reg).unwrap_or(0);
+            let actual = self.read_register_value(reg)?;
+            if actual != expected {
+                drift.push(RegisterDrift { register: reg, expected, actual });
+            }
+        }
+        Ok(drift)
+    }
+
+    /// Writes the shadow copy back over the registers reported by verify().
+    pub fn restore_registers(&mut self, drift: &[RegisterDrift]) -> Result<()> {
+        for entry in drift {
+            self.write_register_value(entry.register, entry.expected)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_standby(&mut self, mode: StandbyMode) -> Result<()> {
+        self.write_command(Command::SetStandby, &[mode as u8])
+    }
+
+    /// Puts the chip into its lowest power mode. With retain_config the configuration is saved with
+    /// SetSaveContext and kept through sleep, so wake() does not have to set it up again; with
+    /// retain_buffer the packet data buffer survives as well.
+    pub fn sleep(&mut self, retain_config: bool, retain_buffer: bool) -> Result<()> {
+        self.set_standby(StandbyMode::Rc)?;
+        if retain_config {
+            self.save_context()?;
+        }
+
+        let mut config = 0x00;
+        if retain_config {
+            config |= SLEEP_RETAIN_DATA_RAM;
+        }
+        if retain_buffer {
+            config |= SLEEP_RETAIN_DATA_BUFFER;
+        }
+        self.write_command(Command::SetSleep, &[config])?;
+        self.sleeping = Some(retain_config);
+        self.sleep_regs = Some(self.regs.clone());
+        Ok(())
+    }
+
+    /// Copies the current register configuration into data RAM, where a sleep with retention
+    /// keeps it.
+    pub fn save_context(&mut self) -> Result<()> {
+        self.write_command(Command::SetSaveContext, &[])
+    }
+
+    /// Wakes the chip from sleep. If the configuration was retained and still reads back as
+    /// expected, that is a warm start and the chip is ready to go. Otherwise it falls back to a
+    /// full init(), applies the cached modem configuration, frequency and output power again and
+    /// writes back any register set through write_register() before sleeping.
+    pub fn wake(&mut self) -> Result<WakeUp> {
+        // a falling edge on NSS wakes the chip, which lowers BUSY once it is in standby again
+        gpio::set_low(self.cs);
+        let awake = self.wait_busy_for(SX1280_RESET_TIMEOUT);
+        gpio::set_high(self.cs);
+
+        let retained = self.sleeping.take().unwrap_or(false);
+        let saved = self.sleep_regs.take();
+        if awake.is_ok() && retained && matches!(self.context_intact(), Ok(true)) {
+            return Ok(WakeUp::Warm);
+        }
+
+        self.init()?;
+        self.reapply_configuration()?;
+        if let Some(saved) = saved {
+            self.restore_shadow(&saved)?;
+        }
+        Ok(WakeUp::Cold)
+    }
+
+    // A chip that lost its context comes back with the default packet type and registers
+    fn context_intact(&mut self) -> Result<bool> {
+        Ok(self.get_packet_type()? == self.packet_type && self.verify()?.is_empty())
+    }
+
+    // Writes every configuration register that differs from an earlier shadow copy back to the chip
+    fn restore_shadow(&mut self, saved: &SX1280_registers) -> Result<()> {
+        for reg in MIRRORED_REGISTERS.into_iter().filter(|reg| !reg.is_volatile()) {
+            let value = saved.get(reg).unwrap_or(0);
+            if self.regs.get(reg) != Some(value) {
+                self.write_register_value(reg, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Sends everything the driver has cached to a freshly initialised chip
+    fn reapply_configuration(&mut self) -> Result<()> {
+        let (lora, ranging, flrc, gfsk, ble) = (self.lora, self.ranging, self.flrc, self.gfsk, self.ble);
+        match self.packet_type {
+            PACKET_TYPE_LORA => self.configure_lora(&lora)?,
+            PACKET_TYPE_RANGING => self.configure_ranging(&ranging)?,
+            PACKET_TYPE_FLRC => self.configure_flrc(&flrc)?,
+            PACKET_TYPE_GFSK => self.configure_gfsk(&gfsk)?,
+            PACKET_TYPE_BLE => self.configure_ble(&ble)?,
+            modem => return Err(Sx1280Error::WrongModem(modem)),
+        }
+        if let Some(frf) = self.frf {
+            self.set_rf_frequency(frf)?;
+        }
+        self.set_tx_params(self.power, self.pa_ramp.param())
+    }
+
+    /// Selects the LDO or the DC-DC converter as the chip's supply. The regulator can only be
+    /// changed in STDBY_RC, so the chip is put there first. The choice is kept across init(), so
+    /// it can be made before bringing the chip up.
+    pub fn set_regulator_mode(&mut self, mode: RegulatorMode) -> Result<()> {
+        self.set_standby(StandbyMode::Rc)?;
+        self.write_command(Command::SetRegulatorMode, &[mode as u8])?;
+        self.regulator_mode = mode;
+        Ok(())
+    }
+
+    pub fn regulator_mode(&self) -> RegulatorMode {
+        self.regulator_mode
+    }
+
+    /// Typical supply current range in mA in the given mode with the current regulator, for
+    /// battery budgets. None where the datasheet gives no figure, see current_consumption().
+    pub fn estimated_current(&self, mode: RadioMode) -> Option<RangeInclusive<f32>> {
+        current_consumption(mode, self.regulator_mode)
+    }
+
+    pub fn set_buffer_base_address(&mut self, tx_base: u8, rx_base: u8) -> Result<()> {
+        self.write_command(Command::SetBufferBaseAddress, &[tx_base, rx_base])?;
+        self.tx_base = tx_base;
+        self.rx_base = rx_base;
+        Ok(())
+    }
+
+    pub fn set_packet_type(&mut self, packet_type: u8) -> Result<()> {
+        self.write_command(Command::SetPacketType, &[packet_type])?;
+        self.packet_type = packet_type;
+        Ok(())
+    }
+
+    pub fn get_packet_type(&mut self) -> Result<u8> {
+        let mut data = [0u8];
+        self.read_command(Command::GetPacketType, &[], &mut data)?;
+        Ok(data[0])
+    }
+
+    pub fn set_rf_frequency(&mut self, frf: u32) -> Result<()> {
+        let data = [((frf >> 16) & 0xFF) as u8, ((frf >> 8) & 0xFF) as u8, (frf & 0xFF) as u8];
+        self.write_command(Command::SetRfFrequency, &data)?;
+        self.frf = Some(frf);
+        Ok(())
+    }
+
+    pub fn set_tx_params(&mut self, power: u8, ramp_time: u8) -> Result<()> {
+        self.write_command(Command::SetTxParams, &[power, ramp_time])
+    }
+
+    pub fn set_modulation_params(&mut self, mod_param1: u8, mod_param2: u8, mod_param3: u8) -> Result<()> {
+        self.write_command(Command::SetModulationParams, &[mod_param1, mod_param2, mod_param3])
+    }
+
+    pub fn write_buffer(&mut self, offset: u8, data: &[u8]) -> Result<()> {
+        let mut params = vec![offset];
+        params.extend_from_slice(data);
+        self.write_command(Command::WriteBuffer, &params)
+    }
+
+    pub fn read_buffer(&mut self, offset: u8, data: &mut [u8]) -> Result<()> {
+        self.read_command(Command::ReadBuffer, &[offset], data)
+    }
+
+    /// Starts transmitting whatever is in the buffer. The chip gives up with RxTxTimeout after
+    /// timeout; zero disables the timeout.
+    pub fn set_tx(&mut self, timeout: Duration) -> Result<()> {
+        self.write_command(Command::SetTx, &encode_timeout(timeout)?)
+    }
+
+    /// Listens for one packet, giving up with RxTxTimeout after timeout; zero waits indefinitely.
+    pub fn set_rx(&mut self, timeout: Duration) -> Result<()> {
+        self.write_command(Command::SetRx, &encode_timeout(timeout)?)
+    }
+
+    /// Keeps receiving packets until the chip is put back into standby.
+    pub fn set_rx_continuous(&mut self) -> Result<()> {
+        self.write_command(Command::SetRx, &[0x00, 0xFF, 0xFF])
+    }
+
+    /// Keys up an unmodulated carrier at the current frequency and output power until the chip is
+    /// put back into standby.
+    pub fn set_tx_continuous_wave(&mut self) -> Result<()> {
+        self.write_command(Command::SetTxContinuousWave, &[])
+    }
+
+    /// Sends the active modem's preamble over and over until the chip is put back into standby.
+    pub fn set_tx_continuous_preamble(&mut self) -> Result<()> {
+        self.write_command(Command::SetTxContinuousPreamble, &[])
+    }
+
+    /// Alternates between listening for rx and sleeping for sleep until a packet comes in. A
+    /// preamble found while listening keeps the chip in RX until the packet is done.
+    pub fn set_rx_duty_cycle(&mut self, rx: Duration, sleep: Duration) -> Result<()> {
+        self.write_command(Command::SetRxDutyCycle, &encode_duty_cycle(rx, sleep)?)
+    }
+
+    /// In long preamble mode a transmitter keeps sending preamble for the whole SetTx period before
+    /// the packet, so it can be longer than the packet parameters can encode.
+    pub fn set_long_preamble(&mut self, enable: bool) -> Result<()> {
+        self.write_command(Command::SetLongPreamble, &[enable as u8])
+    }
+
+    /// Enables the IRQs in irq_mask and routes them to the DIO pins as given by mapping.
+    pub fn set_dio_irq_params(&mut self, irq_mask: Irq, mapping: &DioMapping) -> Result<()> {
+        let mut params = Vec::with_capacity(8);
+        for mask in [irq_mask, mapping.dio1, mapping.dio2, mapping.dio3] {
+            params.extend_from_slice(&mask.bits().to_be_bytes());
+        }
+        self.write_command(Command::SetDioIrqParams, &params)
+    }
+
+    pub fn get_irq_status(&mut self) -> Result<Irq> {
+        let mut data = [0u8; 2];
+        self.read_command(Command::GetIrqStatus, &[], &mut data)?;
+        Ok(Irq::from_bits(u16::from_be_bytes(data)))
+    }
+
+    pub fn clear_irq_status(&mut self, irq: Irq) -> Result<()> {
+        self.write_command(Command::ClearIrqStatus, &irq.bits().to_be_bytes())
+    }
+
+    /// Length and buffer offset of the last received packet.
+    pub fn get_rx_buffer_status(&mut self) -> Result<(usize, u8)> {
+        let mut data = [0u8; 2];
+        self.read_command(Command::GetRxBufferStatus, &[], &mut data)?;
+
+        let length = match self.packet_type {
+            // BLE leaves the 2-byte PDU header out of the reported length
+            PACKET_TYPE_BLE => data[0] as usize + 2,
+            // implicit header packets have no length field to report, so use the configured one
+            PACKET_TYPE_LORA if matches!(self.lora.header, LoRaHeader::Implicit(_)) => {
+                self.read_register_value(Register::PayloadLength)? as usize
+            }
+            _ => data[0] as usize,
+        };
+        Ok((length, data[1]))
+    }
+
+    // Enables the IRQs in irq_mask on top of the fixed DIO mapping, routes them to the watched DIO
+    // if there is one, and clears any left over from earlier operations
+    fn enable_irqs(&mut self, irq_mask: Irq) -> Result<()> {
+        let mut mapping = self.dio_mapping;
+        if let Some((dio, _)) = self.irq_pin {
+            mapping.route(dio, irq_mask);
+        }
+        self.set_dio_irq_params(irq_mask | mapping.all(), &mapping)?;
+        self.clear_irq_status(Irq::ALL)
+    }
+
+    // Waits until one of the IRQs in irq_mask fires, sleeping on the IRQ pin if available and
+    // polling GetIrqStatus otherwise. Other IRQs raised in the meantime are cleared, or one routed
+    // to the pin would hold it high and the wait would never sleep again. The chip's own timeout
+    // should always get there first; the extra margin only catches a chip that stopped
+    // responding. A zero timeout waits forever.
+    fn poll_irq(&mut self, irq_mask: Irq, timeout: Duration) -> Result<Irq> {
+        let deadline = (!timeout.is_zero()).then(|| Instant::now() + timeout + IRQ_POLL_MARGIN);
+        loop {
+            let raised = match self.irq_pin {
+                Some((_, pin)) => {
+                    let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                    gpio::wait_high(pin, remaining)
+                }
+                None => true,
+            };
+            if raised {
+                let irq = self.get_irq_status()?;
+                if irq.intersects(irq_mask) {
+                    return Ok(irq);
+                }
+                if !irq.is_empty() {
+                    self.clear_irq_status(irq)?;
+                }
+            }
+            if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                return Ok(Irq::RX_TX_TIMEOUT);
+            }
+            thread::sleep(Duration::from_micros(100));
+        }
+    }
+
+    /// Runs the radio from interrupts: sleeps until the IRQ pin rises, then reads and clears the
+    /// IRQs and sends what happened to events. The radio is only locked while it is being talked
+    /// to, so other threads can start operations with start_transmit() and start_receive() in
+    /// between; the blocking calls would race the loop for the IRQs. Returns once the receiving
+    /// end of events has hung up, which is noticed at the next event.
+    pub fn event_loop(radio: &Mutex<SX1280>, events: Sender<RadioEvent>) -> Result<()> {
+        let pin = match radio.lock().unwrap_or_else(PoisonError::into_inner).irq_pin {
+            Some((_, pin)) => pin,
+            None => return Err(Sx1280Error::NoIrqPin),
+        };
+        loop {
+            gpio::wait_high(pin, None);
+            let event = radio.lock().unwrap_or_else(PoisonError::into_inner).next_event()?;
+            if let Some(event) = event {
+                if events.send(event).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // Reads and clears the pending IRQs and turns them into the event they stand for
+    fn next_event(&mut self) -> Result<Option<RadioEvent>> {
+        let irq = self.get_irq_status()?;
+        if irq.is_empty() {
+            return Ok(None);
+        }
+        self.clear_irq_status(Irq::ALL)?;
+
+        let event = if irq.contains(Irq::TX_DONE) {
+            RadioEvent::TxDone
+        } else if irq.intersects(RX_EVENTS) {
+            match self.read_packet(irq) {
+                Ok(Some(packet)) => RadioEvent::RxDone(packet),
+                Ok(None) => RadioEvent::Timeout,
+                Err(Sx1280Error::CrcError) => RadioEvent::CrcError,
+                Err(Sx1280Error::HeaderError) => RadioEvent::HeaderError,
+                Err(err) => return Err(err),
+            }
+        } else if irq.contains(Irq::CAD_DONE) {
+            RadioEvent::CadDone { detected: irq.contains(Irq::CAD_DETECTED) }
+        } else if irq.contains(Irq::PREAMBLE_DETECTED) {
+            RadioEvent::PreambleDetected
         } else {
-            self::coding_rate_lora = cr - 4;
-        }
-        return SetModulationParams(self::spreading_factor, self::bandwidth, self::coding_rate_lora);
-  
-    // FLRC
-    } else if modem == PACKET_TYPE_FLRC {
-        CheckRange(cr, 2, 4, ERR_INVALID_CODING_RATE);
-    
-        // update modulation parameters
-        self::codingrateflrc = (cr - 2) * 2;
-        return setModulationParams(self::bit_rate, self::codingrate_flrc, self::shaping);
-    }
-  
-    return ERR_WRONG_MODEM;
-}
-  
-fn SetOutputPower(pwr: i8) -> i16 {
-    ChipSelect(pwr, -18, 13, ERR_INVALID_OUTPUT_POWER);
-    self::power = pwr + 18;
-    return SetTxParams(self::power);
-}
-  
-fn SetPreambleLength(preamble_length: u32) -> i16 {
-  let modem: u8 = GetPacketType();
-  if (modem == PACKET_TYPE_LORA) || (modem == PACKET_TYPE_RANGING) {
-    // LoRa or ranging
-    CheckRange(preamble_length, 2, 491520, ERR_INVALID_PREAMBLE_LENGTH);
+            RadioEvent::Other(irq)
+        };
+        Ok(Some(event))
+    }
+
+    fn set_modulation_params_lora(&mut self) -> Result<()> {
+        let [sf, bw, cr] = self.lora.modulation_params();
+        self.set_modulation_params(sf, bw, cr)?;
+
+        // LoRa and ranging need two register tweaks after every SetModulationParams - SX1280
+        // datasheet v3.0 section 13.4.1
+        if matches!(self.packet_type, PACKET_TYPE_LORA | PACKET_TYPE_RANGING) {
+            self.write_register_value(Register::SFAdditionalConfiguration, self.lora.sf.additional_configuration() as u64)?;
+            self.write_field(Field::FREQUENCY_ERROR_CORRECTION, 0x01)?;
+        }
+        Ok(())
+    }
+
+    fn set_packet_params_lora(&mut self) -> Result<()> {
+        let data = self.lora.packet_params(self.payload_length)?;
+        self.write_command(Command::SetPacketParams, &data)
+    }
+
+    pub fn lora_config(&self) -> &LoRaConfig {
+        &self.lora
+    }
+
+    /// Switches to LoRa and applies the whole modem configuration in one go. Nothing is sent to the
+    /// chip if the configuration is invalid.
+    pub fn configure_lora(&mut self, config: &LoRaConfig) -> Result<()> {
+        config.validate(false)?;
 
-    // check preamble length is even - no point even trying odd numbers
-    if preamble_length % 2 != 0 {
-        return ERR_INVALID_PREAMBLE_LENGTH;
+        self.set_packet_type(PACKET_TYPE_LORA)?;
+        self.lora = *config;
+        self.set_modulation_params_lora()?;
+        self.set_packet_params_lora()
     }
 
-    // calculate exponent and mantissa values (use the next longer preamble if there's no exact match)
-    let len: u32 = 0;
-    for e: u8 in 1..15 {
-      for m: u8 in 1..15 {
-        len = m * (1 as u32 << e);
-        if len >= preamble_length {
-          break;
+    // LoRa and ranging share modulation and packet parameters
+    fn check_lora_modem(&mut self) -> Result<u8> {
+        let modem = self.get_packet_type()?;
+        if (modem != PACKET_TYPE_LORA) && (modem != PACKET_TYPE_RANGING) {
+            return Err(Sx1280Error::WrongModem(modem));
         }
-      }
-      if len >= preamble_length {
-        break;
-      }
+        Ok(modem)
     }
 
-    // update packet parameters
-    self::preamble_length_lora = (e << 4) | m;
-    return SetPacketParams(self::preamble_length_lora, self::header_type, self::payload_length, self::crc_lora, self::invert_iq_enabled);
+    // Applies a single changed LoRa parameter, rolling the cached configuration back if rejected
+    fn update_lora(&mut self, config: LoRaConfig, packet_params: bool) -> Result<()> {
+        let modem = self.check_lora_modem()?;
+        config.validate(modem == PACKET_TYPE_RANGING)?;
 
-  } else if (modem == PACKET_TYPE_GFSK) || (modem == PACKET_TYPE_FLRC) {
-    // GFSK or FLRC
-    CheckRange(preamble_length, 4, 32, ERR_INVALID_PREAMBLE_LENGTH);
+        let previous = self.lora;
+        self.lora = config;
+        let mut result = if packet_params {
+            self.set_packet_params_lora()
+        } else {
+            self.set_modulation_params_lora()
+        };
+        if result.is_ok() && modem == PACKET_TYPE_RANGING {
+            // a new SF or bandwidth needs its own calibration
+            self.ranging.lora = config;
+            result = self.set_ranging_calibration();
+        }
+        if result.is_err() {
+            self.lora = previous;
+        }
+        result
+    }
 
-    // check preamble length is multiple of 4
-    if(preamble_length % 4 != 0) {
-      return ERR_INVALID_PREAMBLE_LENGTH;
+    fn set_modulation_params_flrc(&mut self) -> Result<()> {
+        let [br, cr, bt] = self.flrc.modulation_params();
+        self.set_modulation_params(br, cr, bt)
     }
 
-    // update packet parameters
-    self::preamble_length_gfsk = ((preamble_length / 4) - 1) << 4;
-    return SetPacketParamsGFSK(self::preamble_length_gfsk, self::sync_word_len, self::sync_word_match, self::crc_gfsk, self::whitening);
-  }
+    fn set_packet_params_flrc(&mut self) -> Result<()> {
+        let data = self.flrc.packet_params(self.payload_length);
+        self.write_command(Command::SetPacketParams, &data)
+    }
 
-  return ERR_WRONG_MODEM;
-}
-  
-fn SetBitRate(br: f32) -> i16 {
-  // check active modem
-  let modem: u8 = GetPacketType();
-
-  // GFSK/BLE
-  if (modem == PACKET_TYPE_GFSK) || (modem == PACKET_TYPE_BLE) {
-    match br as u16 {
-      125 => self::bit_rate = SX128X_BLE_GFSK_BR_0_125_BW_0_3,
-      250 => self::bit_rate = SX128X_BLE_GFSK_BR_0_250_BW_0_6,
-      400 => self::bit_rate = SX128X_BLE_GFSK_BR_0_400_BW_1_2,
-      500 => self::bit_rate = SX128X_BLE_GFSK_BR_0_500_BW_1_2,
-      800 => self::bit_rate = SX128X_BLE_GFSK_BR_0_800_BW_2_4,
-      1000 => self::bit_rate = SX128X_BLE_GFSK_BR_1_000_BW_2_4,
-      1600 => self::bit_rate = SX128X_BLE_GFSK_BR_1_600_BW_2_4,
-      2000 => self::bit_rate = SX128X_BLE_GFSK_BR_2_000_BW_2_4,
-      _ => return ERR_INVALID_BIT_RATE
-    }
-
-    // update modulation parameters
-    self::bit_rate_kbps = br as u16;
-    return SetModulationParams(self::bit_rate, self::mod_index, self::shaping);
-
-  // FLRC
-  } else if modem == PACKET_TYPE_FLRC {
-    match br as u16 {
-      260 => self::bit_rate = SX128X_FLRC_BR_0_260_BW_0_3,
-      325 => self::bit_rate = SX128X_FLRC_BR_0_325_BW_0_3,
-      520 => self::bit_rate = SX128X_FLRC_BR_0_520_BW_0_6,
-      650 => self::bit_rate = SX128X_FLRC_BR_0_650_BW_0_6,
-      1000 => self::bit_rate = SX128X_FLRC_BR_1_000_BW_1_2,
-      1300 => self::bit_rate = SX128X_FLRC_BR_1_300_BW_1_2,
-      _ => return ERR_INVALID_BIT_RATE
-    }
-
-    // update modulation parameters
-    self::bit_rate_kbps = br as u16;
-    return SetModulationParams(self::bit_rate, self::coding_rate_flrc, self::shaping);
-
-  }
-
-  return ERR_WRONG_MODEM;
-}
+    // GFSK and FLRC sync words are right-aligned in the 5-byte sync address registers, so a
+    // shorter word only uses the last bytes of each
+    fn set_sync_words(&mut self, words: [u64; 3]) -> Result<()> {
+        let regs = [Register::SyncAddress1, Register::SyncAddress2, Register::SyncAddress3];
+        for (reg, word) in regs.into_iter().zip(words) {
+            self.write_register_value(reg, word)?;
+        }
+        Ok(())
+    }
 
-fn SetFrequencyDeviation(freq_dev: f32) -> i16 {
-  // check active modem
-  let modem: u8 = GetPacketType();
-  if !((modem == PACKET_TYPE_GFSK) || (modem == PACKET_TYPE_BLE)) {
-    return ERR_WRONG_MODEM;
-  }
-
-  // set frequency deviation to lowest available setting (required for digimodes)
-  let new_freq_dev: f32 = feq_dev;
-  if freq_dev < 0.0 {
-    new_freq_dev = 62.5;
-  }
-
-  CheckRange(freq_dev, 62.5, 1000.0, ERR_INVALID_FREQUENCY_DEVIATION);
-
-  // override for the lowest possible frequency deviation - required for some PhysicalLayer protocols
-  if new_freq_dev == 0.0 {
-    self::mod_index = SX128X_BLE_GFSK_MOD_IND_0_35;
-    self::bit_rate = SX128X_BLE_GFSK_BR_0_125_BW_0_3;
-    return SetModulationParams(self::bandwidth, self::mod_index, self::shaping);
-  }
-
-  // update modulation parameters
-  let mod_index: u8 = ((8.0 * (new_freq_dev / (self::bit_rate_kbps as f32))) - 1.0) as u8;
-  if mod_index > SX128X_BLE_GFSK_MOD_IND_4_00 {
-    return ERR_INVALID_MODULATION_PARAMETERS;
-  }
-
-  // update modulation parameters
-  self::mod_index = mod_index;
-  return SetModulationParams(self::bit_rate, self::mod_index, self::shaping);
-}
+    pub fn flrc_config(&self) -> &FlrcConfig {
+        &self.flrc
+    }
 
-fn SetDataShaping(sh: u8) -> i16 {
-  // check active modem
-  let modem: u8 = GetPacketType();
-  if !((modem == PACKET_TYPE_GFSK) || (modem == PACKET_TYPE_BLE) || (modem == PACKET_TYPE_FLRC)) {
-    return ERR_WRONG_MODEM;
-  }
-
-  // set data self::shaping
-  match sh {
-    SHAPING_NONE => self::shaping = SX128X_BLE_GFSK_BT_OFF,
-    SHAPING_0_5 => self::shaping = SX128X_BLE_GFSK_BT_0_5,
-    SHAPING_1_0 => self::shaping = SX128X_BLE_GFSK_BT_1_0,
-    _ => return ERR_INVALID_DATA_SHAPING,
-  }
-
-  // update modulation parameters
-  if (modem == PACKET_TYPE_GFSK) || (modem == PACKET_TYPE_BLE) {
-    return SetModulationParams(self::bit_rate, self::mod_index, self::shaping);
-  } else {
-    return SetModulationParams(self::bit_rate, self::coding_rate_flrc, self::shaping);
-  }
-}
+    /// Switches to FLRC and applies modulation, sync words and packet parameters. Nothing is sent
+    /// to the chip if the configuration is invalid.
+    pub fn configure_flrc(&mut self, config: &FlrcConfig) -> Result<()> {
+        config.validate()?;
+
+        self.set_packet_type(PACKET_TYPE_FLRC)?;
+        self.flrc = *config;
+        self.set_modulation_params_flrc()?;
+        self.set_sync_words(self.flrc.sync_words.map(u64::from))?;
+        self.set_packet_params_flrc()
+    }
+
+    // Re-applies the FLRC configuration with one parameter changed, rolling back if rejected
+    fn update_flrc(&mut self, config: FlrcConfig) -> Result<()> {
+        let modem = self.get_packet_type()?;
+        if modem != PACKET_TYPE_FLRC {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
+        config.validate()?;
+
+        let previous = self.flrc;
+        self.flrc = config;
+        let result = self.set_modulation_params_flrc()
+            .and_then(|_| self.set_sync_words(self.flrc.sync_words.map(u64::from)))
+            .and_then(|_| self.set_packet_params_flrc());
+        if result.is_err() {
+            self.flrc = previous;
+        }
+        result
+    }
+
+    fn set_modulation_params_gfsk(&mut self) -> Result<()> {
+        let [br, mod_index, bt] = self.gfsk.modulation_params()?;
+        self.set_modulation_params(br, mod_index, bt)
+    }
+
+    fn set_packet_params_gfsk(&mut self) -> Result<()> {
+        let data = self.gfsk.packet_params(self.payload_length);
+        self.write_command(Command::SetPacketParams, &data)
+    }
+
+    // The GFSK CRC seed is the two bytes at CrcInitialValue, which overlap the low end of the
+    // BLE-sized CrcPolynomialSeed
+    fn set_crc_gfsk(&mut self) -> Result<()> {
+        self.write_register_value(Register::CrcPolynomialDefinition, self.gfsk.crc_polynomial as u64)?;
+        self.write_register_value(Register::CrcInitialValue, self.gfsk.crc_seed as u64)
+    }
+
+    fn apply_gfsk(&mut self) -> Result<()> {
+        self.set_modulation_params_gfsk()?;
+        self.set_sync_words(self.gfsk.sync_words)?;
+        self.set_crc_gfsk()?;
+        self.set_packet_params_gfsk()
+    }
+
+    pub fn gfsk_config(&self) -> &GfskConfig {
+        &self.gfsk
+    }
+
+    /// Switches to GFSK and applies modulation, sync words, CRC and packet parameters. Nothing is
+    /// sent to the chip if the configuration is invalid.
+    pub fn configure_gfsk(&mut self, config: &GfskConfig) -> Result<()> {
+        config.validate()?;
+
+        self.set_packet_type(PACKET_TYPE_GFSK)?;
+        self.gfsk = *config;
+        self.apply_gfsk()
+    }
+
+    // Re-applies the GFSK configuration with one parameter changed, rolling back if rejected
+    fn update_gfsk(&mut self, config: GfskConfig) -> Result<()> {
+        let modem = self.get_packet_type()?;
+        if modem != PACKET_TYPE_GFSK {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
+        config.validate()?;
+
+        let previous = self.gfsk;
+        self.gfsk = config;
+        let result = self.apply_gfsk();
+        if result.is_err() {
+            self.gfsk = previous;
+        }
+        result
+    }
+
+    fn set_packet_params_ble(&mut self) -> Result<()> {
+        let data = self.ble.packet_params();
+        self.write_command(Command::SetPacketParams, &data)
+    }
+
+    // Everything but the packet type: PHY, packet parameters, addressing and the channel
+    fn apply_ble(&mut self) -> Result<()> {
+        let [br, mod_index, bt] = BLE_MODULATION_PARAMS;
+        self.set_modulation_params(br, mod_index, bt)?;
+        self.set_packet_params_ble()?;
+        self.write_register_value(Register::AccessAddress, self.ble.access_address as u64)?;
+        self.write_register_value(Register::CrcPolynomialSeed, self.ble.crc_init as u64)?;
+        self.set_frequency(ble_channel_frequency(self.ble.channel)?)?;
+        self.write_register_value(Register::WhiteningInitialValue, ble_whitening_seed(self.ble.channel) as u64)
+    }
+
+    pub fn ble_config(&self) -> &BleConfig {
+        &self.ble
+    }
+
+    /// Switches to BLE and applies the whole configuration, including the channel. Nothing is sent
+    /// to the chip if the configuration is invalid.
+    pub fn configure_ble(&mut self, config: &BleConfig) -> Result<()> {
+        config.validate()?;
+
+        self.set_packet_type(PACKET_TYPE_BLE)?;
+        self.ble = *config;
+        self.apply_ble()
+    }
+
+    // Re-applies the BLE configuration with one parameter changed, rolling back if rejected
+    fn update_ble(&mut self, config: BleConfig) -> Result<()> {
+        let modem = self.get_packet_type()?;
+        if modem != PACKET_TYPE_BLE {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
+        config.validate()?;
+
+        let previous = self.ble;
+        self.ble = config;
+        let result = self.apply_ble();
+        if result.is_err() {
+            self.ble = previous;
+        }
+        result
+    }
+
+    /// Tunes to a BLE channel index (0 to 39) and reseeds whitening to match it.
+    pub fn set_ble_channel(&mut self, channel: u8) -> Result<()> {
+        self.update_ble(BleConfig { channel, ..self.ble })
+    }
+
+    /// Sends the same PDU once on each of the three advertising channels, as an advertising event
+    /// does, then returns to the channel that was configured before.
+    pub fn advertise_ble(&mut self, pdu: &[u8]) -> Result<()> {
+        let channel = self.ble.channel;
+        for adv_channel in BLE_ADVERTISING_CHANNELS {
+            self.set_ble_channel(adv_channel)?;
+            self.transmit(pdu)?;
+        }
+        self.set_ble_channel(channel)
+    }
+
+    /// Sends one packet and waits for it to go out. In BLE mode data is the raw PDU, header and
+    /// payload, without access address or CRC.
+    pub fn transmit(&mut self, data: &[u8]) -> Result<TxReport> {
+        self.start_transmit(data)?;
+        let start = Instant::now();
+
+        let irq = self.poll_irq(Irq::TX_DONE | Irq::RX_TX_TIMEOUT, self.tx_timeout(data.len())?)?;
+        let airtime = start.elapsed();
+        self.clear_irq_status(Irq::ALL)?;
+        if !irq.contains(Irq::TX_DONE) {
+            self.set_standby(StandbyMode::Rc)?;
+            return Err(Sx1280Error::TxTimeout);
+        }
+        Ok(TxReport { length: data.len(), airtime })
+    }
+
+    /// Loads data and starts sending it without waiting for TxDone, for use with event_loop().
+    pub fn start_transmit(&mut self, data: &[u8]) -> Result<()> {
+        self.set_standby(StandbyMode::Rc)?;
+        self.prepare_payload(data)?;
+        self.write_buffer(self.tx_base, data)?;
+
+        let timeout = self.tx_timeout(data.len())?;
+        self.enable_irqs(Irq::TX_DONE | Irq::RX_TX_TIMEOUT)?;
+        self.set_tx(timeout)
+    }
+
+    /// Sends data behind a preamble lasting at least preamble, so that a receiver in RX duty cycle
+    /// wakes up during it. The preamble has to cover the receiver's sleep period plus one listen
+    /// window.
+    pub fn transmit_wake(&mut self, data: &[u8], preamble: Duration) -> Result<TxReport> {
+        self.set_standby(StandbyMode::Rc)?;
+        self.prepare_payload(data)?;
+        self.write_buffer(self.tx_base, data)?;
+        self.set_long_preamble(true)?;
+        self.enable_irqs(Irq::TX_DONE | Irq::RX_TX_TIMEOUT)?;
+        self.set_tx(preamble)?;
+        let start = Instant::now();
+
+        // the SetTx period ends the preamble rather than the transmission, so only TxDone counts
+        let irq = self.tx_timeout(data.len()).and_then(|timeout| self.poll_irq(Irq::TX_DONE, preamble + timeout));
+        let airtime = start.elapsed();
+        self.clear_irq_status(Irq::ALL)?;
+        self.set_long_preamble(false)?;
+        if !irq?.contains(Irq::TX_DONE) {
+            self.set_standby(StandbyMode::Rc)?;
+            return Err(Sx1280Error::TxTimeout);
+        }
+        Ok(TxReport { length: data.len(), airtime })
+    }
+
+    /// With auto FS the chip drops back to frequency synthesis instead of standby after a packet,
+    /// which cuts the time the next RX or TX takes to start.
+    pub fn set_auto_fs(&mut self, enable: bool) -> Result<()> {
+        self.write_command(Command::SetAutoFS, &[enable as u8])
+    }
+
+    /// Makes the chip transmit the buffer by itself delay after every RxDone. The shortest
+    /// possible delay is just over 33 us; zero turns auto TX off.
+    pub fn set_auto_tx(&mut self, delay: Duration) -> Result<()> {
+        let time = if delay.is_zero() {
+            0
+        } else {
+            match delay.checked_sub(AUTO_TX_OFFSET).map(|time| time.as_micros()) {
+                Some(time) if (1..=0xFFFF).contains(&time) => time as u16,
+                _ => return Err(Sx1280Error::InvalidTimeout(delay)),
+            }
+        };
+        self.write_command(Command::SetAutoTx, &time.to_be_bytes())
+    }
+
+    /// Listens for a packet and has the chip send response exactly delay after it arrives,
+    /// without waiting for the host, e.g. to acknowledge an uplink command in time. Returns the
+    /// received packet once the response is out, or None if nothing arrived within timeout.
+    /// The response is sent even if the packet turns out to have a bad CRC. Both share one set of
+    /// packet parameters, so a variable length packet may be no longer than the response. The
+    /// packet is received into the buffer behind the response, so a response of more than 128
+    /// bytes (or one that leaves too little room for a fixed length or BLE packet) is rejected
+    /// with InvalidPayloadLength before anything is sent to the chip.
+    pub fn respond_after_receive(&mut self, response: &[u8], delay: Duration, timeout: Duration) -> Result<Option<RxPacket>> {
+        check_response_space(response.len(), self.max_rx_length(response.len()))?;
+
+        self.set_standby(StandbyMode::Rc)?;
+        self.prepare_payload(response)?;
+
+        // the incoming packet must not overwrite the response before it has gone out
+        let (tx_base, rx_base) = (self.tx_base, self.rx_base);
+        self.set_buffer_base_address(0x00, response.len() as u8)?;
+        self.write_buffer(0x00, response)?;
+        let result = self.receive_and_respond(response.len(), delay, timeout);
+
+        self.set_auto_tx(Duration::ZERO)?;
+        self.set_auto_fs(false)?;
+        self.set_standby(StandbyMode::Rc)?;
+        self.set_buffer_base_address(tx_base, rx_base)?;
+        result
+    }
+
+    // Longest packet the receiver lets in with the packet parameters set up for sending a packet
+    // of length bytes
+    fn max_rx_length(&self, length: usize) -> usize {
+        match self.packet_type {
+            PACKET_TYPE_LORA => self.lora.max_rx_length(length),
+            PACKET_TYPE_GFSK => self.gfsk.length.max_rx_length(length),
+            PACKET_TYPE_FLRC => self.flrc.length.max_rx_length(length),
+            // the PDU header in front of the payload
+            PACKET_TYPE_BLE => 2 + self.ble.connection_state.max_payload_length() as usize,
+            _ => 0,
+        }
+    }
+
+    fn receive_and_respond(&mut self, length: usize, delay: Duration, timeout: Duration) -> Result<Option<RxPacket>> {
+        self.set_auto_fs(true)?;
+        self.set_auto_tx(delay)?;
+        self.enable_irqs(RX_EVENTS | Irq::TX_DONE)?;
+        self.set_rx(timeout)?;
+
+        let irq = self.poll_irq(RX_EVENTS, timeout)?;
+        if irq.contains(Irq::RX_DONE) {
+            let sent = self.poll_irq(Irq::TX_DONE, delay + self.tx_timeout(length)?)?;
+            if !sent.contains(Irq::TX_DONE) {
+                return Err(Sx1280Error::TxTimeout);
+            }
+        }
+        self.clear_irq_status(Irq::ALL)?;
+        self.read_packet(irq)
+    }
+
+    /// Transmits an unmodulated carrier at freq MHz and pwr dBm, for bench measurements, until the
+    /// returned guard is dropped. Dropping it puts the chip back into standby, also when unwinding
+    /// from a panic.
+    pub fn continuous_wave(&mut self, freq: f32, pwr: i8) -> Result<TestTx<'_>> {
+        self.start_test_tx(freq, pwr)?;
+        let test = TestTx { radio: self };
+        test.radio.set_tx_continuous_wave()?;
+        Ok(test)
+    }
+
+    /// Like continuous_wave(), but sends an endless preamble of the active modem instead.
+    pub fn continuous_preamble(&mut self, freq: f32, pwr: i8) -> Result<TestTx<'_>> {
+        self.start_test_tx(freq, pwr)?;
+        let test = TestTx { radio: self };
+        test.radio.set_tx_continuous_preamble()?;
+        Ok(test)
+    }
+
+    fn start_test_tx(&mut self, freq: f32, pwr: i8) -> Result<()> {
+        self.set_standby(StandbyMode::Rc)?;
+        self.set_frequency(freq)?;
+        self.set_output_power(pwr)
+    }
+
+    pub fn set_cad_params(&mut self, symbols: u8) -> Result<()> {
+        self.write_command(Command::SetCadParams, &[encode_cad_symbols(symbols)?])
+    }
+
+    /// Runs a LoRa channel activity detection over the given number of symbols (1, 2, 4, 8 or 16)
+    /// and returns whether a LoRa signal was detected. The chip is back in standby afterwards.
+    pub fn cad(&mut self, symbols: u8) -> Result<bool> {
+        self.start_cad(symbols)?;
+
+        // the detection itself takes the listened symbols plus about one more to process them
+        let symbol = 2f32.powi(self.lora.sf.value() as i32) / (self.lora.bw.khz() * 1000.0);
+        let timeout = Duration::from_secs_f32(symbol * (symbols as f32 + 1.0));
+        let irq = self.poll_irq(Irq::CAD_DONE, timeout)?;
+        self.clear_irq_status(Irq::ALL)?;
+        if !irq.contains(Irq::CAD_DONE) {
+            self.set_standby(StandbyMode::Rc)?;
+            return Err(Sx1280Error::CadTimeout);
+        }
+        Ok(irq.contains(Irq::CAD_DETECTED))
+    }
+
+    /// Starts a channel activity detection without waiting for it, for use with event_loop().
+    pub fn start_cad(&mut self, symbols: u8) -> Result<()> {
+        let modem = self.get_packet_type()?;
+        if modem != PACKET_TYPE_LORA {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
+
+        self.set_standby(StandbyMode::Rc)?;
+        self.set_cad_params(symbols)?;
+        self.enable_irqs(Irq::CAD_DONE | Irq::CAD_DETECTED)?;
+        self.write_command(Command::SetCAD, &[])
+    }
+
+    /// Transmits only once a CAD finds the channel clear, backing off for a random time after each
+    /// busy one. Gives up with ChannelBusy once lbt.attempts CADs have all found the channel busy.
+    pub fn transmit_lbt(&mut self, data: &[u8], lbt: &LbtConfig) -> Result<TxReport> {
+        lbt.validate()?;
+
+        for _ in 0..lbt.attempts {
+            if !self.cad(lbt.cad_symbols)? {
+                return self.transmit(data);
+            }
+            thread::sleep(random_backoff(lbt.backoff_min, lbt.backoff_max));
+        }
+        Err(Sx1280Error::ChannelBusy(lbt.attempts))
+    }
+
+    /// Listens for a single packet. Returns None if nothing arrived within timeout; a zero timeout
+    /// listens until a packet comes in. Packets that fail their header or CRC check are errors.
+    pub fn receive(&mut self, timeout: Duration) -> Result<Option<RxPacket>> {
+        self.start_receive(timeout)?;
+
+        let irq = self.poll_irq(RX_EVENTS, timeout)?;
+        self.clear_irq_status(Irq::ALL)?;
+        let packet = self.read_packet(irq)?;
+        if packet.is_none() {
+            self.set_standby(StandbyMode::Rc)?;
+        }
+        Ok(packet)
+    }
+
+    /// Puts the radio in continuous receive and hands back an iterator over the packets it picks
+    /// up. Each call to next() blocks until the next packet; dropping the iterator ends reception.
+    pub fn receive_continuous(&mut self) -> Result<ContinuousRx<'_>> {
+        self.start_receive_continuous()?;
+        Ok(ContinuousRx { radio: self })
+    }
+
+    /// Starts listening for a single packet without waiting for it, for use with event_loop().
+    pub fn start_receive(&mut self, timeout: Duration) -> Result<()> {
+        self.set_standby(StandbyMode::Rc)?;
+        self.prepare_receive()?;
+        self.enable_irqs(RX_EVENTS)?;
+        self.set_rx(timeout)
+    }
+
+    /// Listens in RX duty cycle until a packet arrives, spending most of the time asleep. Returns
+    /// None if nothing arrived within timeout; a zero timeout waits until a packet comes in.
+    pub fn receive_duty_cycle(&mut self, rx: Duration, sleep: Duration, timeout: Duration) -> Result<Option<RxPacket>> {
+        self.start_rx_duty_cycle(rx, sleep)?;
+
+        let irq = self.poll_irq(Irq::RX_DONE | Irq::HEADER_ERROR | Irq::CRC_ERROR, timeout)?;
+        self.clear_irq_status(Irq::ALL)?;
+        let packet = self.read_packet(irq);
+        self.set_standby(StandbyMode::Rc)?;
+        packet
+    }
+
+    /// Starts RX duty cycle without waiting, for use with event_loop(). A preamble heard in one of
+    /// the listen windows is reported as PreambleDetected, ahead of the packet itself.
+    pub fn start_rx_duty_cycle(&mut self, rx: Duration, sleep: Duration) -> Result<()> {
+        self.set_standby(StandbyMode::Rc)?;
+        self.prepare_receive()?;
+        self.enable_irqs(RX_EVENTS | Irq::PREAMBLE_DETECTED)?;
+        self.set_rx_duty_cycle(rx, sleep)
+    }
+
+    /// Starts continuous receive without waiting, for use with event_loop(). Every packet is
+    /// reported until the chip is put back into standby.
+    pub fn start_receive_continuous(&mut self) -> Result<()> {
+        self.set_standby(StandbyMode::Rc)?;
+        self.prepare_receive()?;
+        self.enable_irqs(RX_EVENTS)?;
+        self.set_rx_continuous()
+    }
+
+    // Variable length packets are only accepted up to the payload length in the packet
+    // parameters, so open that up to the maximum before listening
+    fn prepare_receive(&mut self) -> Result<()> {
+        match self.get_packet_type()? {
+            PACKET_TYPE_LORA => {
+                self.payload_length = self.lora.max_payload_length();
+                self.set_packet_params_lora()
+            }
+            PACKET_TYPE_GFSK => {
+                self.payload_length = SX1280_MAX_PACKET_LENGTH;
+                self.set_packet_params_gfsk()
+            }
+            PACKET_TYPE_FLRC => {
+                self.payload_length = FLRC_MAX_PAYLOAD_LENGTH;
+                self.set_packet_params_flrc()
+            }
+            PACKET_TYPE_BLE => Ok(()),
+            modem => Err(Sx1280Error::WrongModem(modem)),
+        }
+    }
+
+    // Turns the IRQs that ended a receive into the packet, an error, or None for a timeout
+    fn read_packet(&mut self, irq: Irq) -> Result<Option<RxPacket>> {
+        if irq.contains(Irq::HEADER_ERROR) {
+            return Err(Sx1280Error::HeaderError);
+        }
+        if irq.contains(Irq::CRC_ERROR) {
+            return Err(Sx1280Error::CrcError);
+        }
+        if !irq.contains(Irq::RX_DONE) {
+            return Ok(None);
+        }
+
+        let (length, offset) = self.get_rx_buffer_status()?;
+        let mut data = vec![0; length];
+        self.read_buffer(offset, &mut data)?;
+        let status = self.get_packet_status()?;
+        Ok(Some(RxPacket { data, status }))
+    }
+
+    /// Signal metrics of the last received packet, decoded for the active modem.
+    pub fn get_packet_status(&mut self) -> Result<PacketStatus> {
+        let mut data = [0u8; 5];
+        self.read_command(Command::GetPacketStatus, &[], &mut data)?;
+
+        match self.packet_type {
+            PACKET_TYPE_LORA | PACKET_TYPE_RANGING => {
+                let fei = self.read_register_value(Register::FeiByte)? as u32;
+                Ok(PacketStatus::lora(data, fei, self.lora.bw.khz()))
+            }
+            PACKET_TYPE_GFSK => Ok(PacketStatus::Gfsk(FskPacketStatus::from_bytes(data))),
+            PACKET_TYPE_FLRC => Ok(PacketStatus::Flrc(FskPacketStatus::from_bytes(data))),
+            PACKET_TYPE_BLE => Ok(PacketStatus::Ble(FskPacketStatus::from_bytes(data))),
+            modem => Err(Sx1280Error::WrongModem(modem)),
+        }
+    }
+
+    // Checks a payload against the active modem and updates the packet parameters with its length
+    fn prepare_payload(&mut self, data: &[u8]) -> Result<()> {
+        let invalid = Sx1280Error::InvalidPayloadLength(data.len());
+        let payload_length = match u8::try_from(data.len()) {
+            Ok(length) if length > 0 => length,
+            _ => return Err(invalid),
+        };
+
+        match self.get_packet_type()? {
+            PACKET_TYPE_LORA => {
+                if payload_length > self.lora.max_payload_length() {
+                    return Err(invalid);
+                }
+                if let LoRaHeader::Implicit(fixed) = self.lora.header {
+                    if payload_length != fixed {
+                        return Err(invalid);
+                    }
+                }
+                self.payload_length = payload_length;
+                self.set_packet_params_lora()
+            }
+            PACKET_TYPE_GFSK => {
+                if let PacketLength::Fixed(fixed) = self.gfsk.length {
+                    if payload_length != fixed {
+                        return Err(invalid);
+                    }
+                }
+                self.payload_length = payload_length;
+                self.set_packet_params_gfsk()
+            }
+            PACKET_TYPE_FLRC => {
+                if !(FLRC_MIN_PAYLOAD_LENGTH..=FLRC_MAX_PAYLOAD_LENGTH).contains(&payload_length)
+                    || matches!(self.flrc.length, PacketLength::Fixed(fixed) if fixed != payload_length)
+                {
+                    return Err(invalid);
+                }
+                self.payload_length = payload_length;
+                self.set_packet_params_flrc()
+            }
+            // the PDU header carries the length, so there is nothing to configure
+            PACKET_TYPE_BLE => self.ble.validate_pdu(data),
+            modem => Err(Sx1280Error::WrongModem(modem)),
+        }
+    }
+
+    /// How long a packet of length bytes occupies the channel with the current configuration.
+    pub fn time_on_air(&self, length: usize) -> Result<Duration> {
+        let length = u8::try_from(length).map_err(|_| Sx1280Error::InvalidPayloadLength(length))?;
+        match self.packet_type {
+            PACKET_TYPE_LORA | PACKET_TYPE_RANGING => lora_time_on_air(&self.lora, length),
+            PACKET_TYPE_FLRC => Ok(flrc_time_on_air(&self.flrc, length)),
+            PACKET_TYPE_GFSK => Ok(gfsk_time_on_air(&self.gfsk, length)),
+            PACKET_TYPE_BLE => Ok(ble_time_on_air(&self.ble, length)),
+            modem => Err(Sx1280Error::WrongModem(modem)),
+        }
+    }
+
+    // SetTx timeout for a packet of length bytes. It only needs to catch a transmission that
+    // never finishes, so it allows twice the time on air.
+    fn tx_timeout(&self, length: usize) -> Result<Duration> {
+        Ok(self.time_on_air(length)? * 2 + TX_TIMEOUT_MARGIN)
+    }
+
+    pub fn ranging_config(&self) -> &RangingConfig {
+        &self.ranging
+    }
+
+    /// Switches to ranging and applies the LoRa parameters of the exchange, the addressing, the
+    /// calibration for its SF/BW and the result filter. Nothing is sent to the chip if the
+    /// configuration is invalid.
+    pub fn configure_ranging(&mut self, config: &RangingConfig) -> Result<()> {
+        config.validate()?;
+
+        self.set_packet_type(PACKET_TYPE_RANGING)?;
+        self.ranging = *config;
+        self.lora = config.lora;
+        self.set_modulation_params_lora()?;
+        self.set_packet_params_lora()?;
+
+        self.write_register_value(Register::RangingRequestAddress, config.address as u64)?;
+        self.write_register_value(Register::RangingDeviceAddress, config.address as u64)?;
+        self.write_field(Field::RANGING_ID_CHECK_LENGTH, config.id_check_length())?;
+        self.set_ranging_calibration()?;
+        self.write_register_value(Register::RangingFilterWindowSize, config.filter_window as u64)?;
+        self.reset_ranging_filter()
+    }
+
+    // The calibration value is the low two bytes of Register::RangingCalibration
+    fn set_ranging_calibration(&mut self) -> Result<()> {
+        let calibration = self.ranging.calibration()?;
+        self.write_register(Register::RangingCalibration.address() + 1, &calibration.to_be_bytes())
+    }
+
+    pub fn set_ranging_role(&mut self, role: RangingRole) -> Result<()> {
+        self.write_command(Command::SetRangingRole, &[role as u8])
+    }
+
+    /// Clears the history the filtered ranging result is averaged over, e.g. after the slave moved.
+    pub fn reset_ranging_filter(&mut self) -> Result<()> {
+        self.write_field(Field::RANGING_FILTER_RESET, 1)
+    }
+
+    /// Runs one exchange as master against the configured slave address. Returns None if the slave
+    /// did not answer within timeout.
+    pub fn range(&mut self, timeout: Duration) -> Result<Option<RangingResult>> {
+        let modem = self.get_packet_type()?;
+        if modem != PACKET_TYPE_RANGING {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
+
+        let mask = Irq::RANGING_MASTER_RESULT_VALID | Irq::RANGING_MASTER_TIMEOUT | Irq::RX_TX_TIMEOUT;
+        self.set_standby(StandbyMode::Rc)?;
+        self.set_ranging_role(RangingRole::Master)?;
+        self.enable_irqs(mask)?;
+        self.set_tx(timeout)?;
+
+        let irq = self.poll_irq(mask, timeout)?;
+        self.clear_irq_status(Irq::ALL)?;
+        if !irq.contains(Irq::RANGING_MASTER_RESULT_VALID) {
+            self.set_standby(StandbyMode::Rc)?;
+            return Ok(None);
+        }
+        self.read_ranging_result().map(Some)
+    }
+
+    /// Listens as slave and answers one ranging request addressed to us. Returns whether a
+    /// response went out before timeout.
+    pub fn respond_to_ranging(&mut self, timeout: Duration) -> Result<bool> {
+        let modem = self.get_packet_type()?;
+        if modem != PACKET_TYPE_RANGING {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
+
+        let mask = Irq::RANGING_SLAVE_RESPONSE_DONE | Irq::RX_TX_TIMEOUT;
+        self.set_standby(StandbyMode::Rc)?;
+        self.set_ranging_role(RangingRole::Slave)?;
+        self.enable_irqs(mask | Irq::RANGING_SLAVE_REQUEST_DISCARD)?;
+        self.set_rx(timeout)?;
+
+        // requests for other addresses are discarded by the chip, which keeps listening
+        let irq = self.poll_irq(mask, timeout)?;
+        self.clear_irq_status(Irq::ALL)?;
+        if !irq.contains(Irq::RANGING_SLAVE_RESPONSE_DONE) {
+            self.set_standby(StandbyMode::Rc)?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Reads the result of the last master exchange in the configured result type, converted to
+    /// metres. The signal power always gets the datasheet's fixed RSSI corrections; the configured
+    /// RssiCorrection, if any, then corrects the distance for it.
+    pub fn read_ranging_result(&mut self) -> Result<RangingResult> {
+        let result_type = self.ranging.result_type;
+
+        // the result registers are only readable with the clock running and the result frozen
+        self.set_standby(StandbyMode::Xosc)?;
+        self.write_field(Field::FREEZE_RANGING_RESULT, 1)?;
+        self.write_field(Field::RANGING_RESULT_MUX, result_type as u8)?;
+        let raw = self.read_register_value(Register::RangingResult)? as u32;
+        let rssi = self.read_register_value(Register::RangingRssi)? as u8;
+        let signal_power = self.get_packet_status()?.signal_power();
+        self.set_standby(StandbyMode::Rc)?;
+
+        let mut distance = ranging_distance(raw, result_type, self.ranging.lora.bw);
+        if let Some(correction) = self.ranging.rssi_correction {
+            distance = correction.apply(distance, signal_power);
+        }
+        Ok(RangingResult { result_type, raw, rssi, signal_power, distance })
+    }
 
-fn SetSyncWord(sync_word: *const u8, len: u8) -> i16 {
-  // check active modem
-  let modem: u8 = GetPacketType();
-  if !((modem == PACKET_TYPE_GFSK) || (modem == PACKET_TYPE_FLRC)) {
-    return ERR_WRONG_MODEM;
-  }
+    pub fn set_advanced_ranging(&mut self, enable: bool) -> Result<()> {
+        self.write_command(Command::SetAdvancedRanging, &[enable as u8])
+    }
+
+    /// Passively listens for a ranging request between two other radios and captures its address
+    /// and timing without answering. Returns None if nothing was overheard within timeout, or
+    /// waits forever if timeout is zero. Uses the LoRa parameters of configure_ranging, and as in
+    /// slave mode only requests matching its address over id_check_bits are captured.
+    pub fn listen_advanced_ranging(&mut self, timeout: Duration) -> Result<Option<AdvancedRangingCapture>> {
+        let modem = self.get_packet_type()?;
+        if modem != PACKET_TYPE_RANGING {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
+
+        let mask = Irq::ADVANCED_RANGING_DONE | Irq::RX_TX_TIMEOUT;
+        self.set_standby(StandbyMode::Rc)?;
+        self.set_ranging_role(RangingRole::Slave)?;
+        self.set_advanced_ranging(true)?;
+        self.enable_irqs(mask)?;
+        // advanced ranging only runs in continuous RX - SX1280 datasheet v3.0 section 14.6.1, so
+        // the timeout is kept by the host
+        self.set_rx_continuous()?;
+
+        let irq = self.poll_irq(mask, timeout);
+        let capture = match irq {
+            Ok(irq) if irq.contains(Irq::ADVANCED_RANGING_DONE) => self.read_advanced_ranging_capture().map(Some),
+            Ok(_) => Ok(None),
+            Err(err) => Err(err),
+        };
+
+        // always leave advanced ranging, or the next normal exchange would not be answered
+        self.set_standby(StandbyMode::Rc)?;
+        self.set_advanced_ranging(false)?;
+        self.clear_irq_status(Irq::ALL)?;
+        capture
+    }
+
+    fn read_advanced_ranging_capture(&mut self) -> Result<AdvancedRangingCapture> {
+        self.set_standby(StandbyMode::Xosc)?;
+        self.write_field(Field::FREEZE_RANGING_RESULT, 1)?;
+        self.write_field(Field::RANGING_RESULT_MUX, RangingResultType::Raw as u8)?;
+        let address = self.read_advanced_ranging_address()?;
+        let raw = self.read_register_value(Register::RangingResult)? as u32;
+        let rssi = self.read_register_value(Register::RangingRssi)? as u8;
+        let signal_power = self.get_packet_status()?.signal_power();
+        Ok(AdvancedRangingCapture { address, raw, rssi, signal_power })
+    }
+
+    // The captured address is read 16 bits at a time from Register::AdvancedRangingAddress, with
+    // Field::ADVANCED_RANGING_ADDRESS_SELECT picking the half - SX1280 datasheet v3.0 section 14.6.1
+    fn read_advanced_ranging_address(&mut self) -> Result<u32> {
+        let mut address = 0;
+        for half in 0..2u8 {
+            self.write_field(Field::ADVANCED_RANGING_ADDRESS_SELECT, half)?;
+            address |= (self.read_register_value(Register::AdvancedRangingAddress)? as u32) << (16 * half);
+        }
+        Ok(address)
+    }
+
+    pub fn set_frequency(&mut self, freq: f32) -> Result<()> {
+        if !(2400.0..=2500.0).contains(&freq) {
+            return Err(Sx1280Error::InvalidFrequency(freq));
+        }
+
+        // calculate raw value
+        let frf = (freq as f64 * (1u32 << SX1280_DIV_EXPONENT) as f64 / SX1280_CRYSTAL_FREQ) as u32;
+
+        self.set_rf_frequency(frf)
+    }
+
+    pub fn set_bandwidth(&mut self, bw: Bandwidth) -> Result<()> {
+        self.update_lora(LoRaConfig { bw, ..self.lora }, false)
+    }
+
+    pub fn set_spreading_factor(&mut self, sf: SpreadingFactor) -> Result<()> {
+        self.update_lora(LoRaConfig { sf, ..self.lora }, false)
+    }
+
+    pub fn set_coding_rate(&mut self, cr: CodingRate) -> Result<()> {
+        self.update_lora(LoRaConfig { cr, ..self.lora }, false)
+    }
+
+    pub fn set_flrc_bit_rate(&mut self, bit_rate: FlrcBitRate) -> Result<()> {
+        self.update_flrc(FlrcConfig { bit_rate, ..self.flrc })
+    }
+
+    pub fn set_flrc_coding_rate(&mut self, cr: FlrcCodingRate) -> Result<()> {
+        self.update_flrc(FlrcConfig { cr, ..self.flrc })
+    }
+
+    pub fn set_header(&mut self, header: LoRaHeader) -> Result<()> {
+        self.update_lora(LoRaConfig { header, ..self.lora }, true)
+    }
+
+    pub fn set_invert_iq(&mut self, invert_iq: bool) -> Result<()> {
+        self.update_lora(LoRaConfig { invert_iq, ..self.lora }, true)
+    }
+
+    pub fn set_output_power(&mut self, pwr: i8) -> Result<()> {
+        if !(-18..=13).contains(&pwr) {
+            return Err(Sx1280Error::InvalidOutputPower(pwr));
+        }
+        self.power = (pwr + 18) as u8;
+        self.set_tx_params(self.power, self.pa_ramp.param())
+    }
+
+    /// Sets how long the PA takes to ramp up at the start of a transmission. Slower ramps keep
+    /// the spectrum cleaner at the cost of a few microseconds.
+    pub fn set_pa_ramp_time(&mut self, ramp: PaRampTime) -> Result<()> {
+        self.set_tx_params(self.power, ramp.param())?;
+        self.pa_ramp = ramp;
+        Ok(())
+    }
+
+    pub fn set_preamble_length(&mut self, preamble_length: u32) -> Result<()> {
+        let modem = self.get_packet_type()?;
+        if (modem == PACKET_TYPE_LORA) || (modem == PACKET_TYPE_RANGING) {
+            // LoRa or ranging
+            return self.update_lora(LoRaConfig { preamble: preamble_length, ..self.lora }, true);
+
+        } else if modem == PACKET_TYPE_FLRC {
+            let preamble_bits = u8::try_from(preamble_length).map_err(|_| Sx1280Error::InvalidPreambleLength(preamble_length))?;
+            return self.update_flrc(FlrcConfig { preamble_bits, ..self.flrc });
+
+        } else if modem == PACKET_TYPE_GFSK {
+            let preamble_bits = u8::try_from(preamble_length).map_err(|_| Sx1280Error::InvalidPreambleLength(preamble_length))?;
+            return self.update_gfsk(GfskConfig { preamble_bits, ..self.gfsk });
+        }
+
+        Err(Sx1280Error::WrongModem(modem))
+    }
+
+    pub fn set_bit_rate(&mut self, bit_rate: GfskBitRate) -> Result<()> {
+        // check active modem
+        let modem = self.get_packet_type()?;
+        if modem == PACKET_TYPE_GFSK {
+            return self.update_gfsk(GfskConfig { bit_rate, ..self.gfsk });
+        }
+
+        Err(Sx1280Error::WrongModem(modem))
+    }
+
+    /// Sets the frequency deviation in kHz, which the chip takes as a modulation index relative to
+    /// the current bit rate.
+    pub fn set_frequency_deviation(&mut self, deviation: f32) -> Result<()> {
+        // check active modem
+        let modem = self.get_packet_type()?;
+        if modem == PACKET_TYPE_GFSK {
+            return self.update_gfsk(GfskConfig { deviation, ..self.gfsk });
+        }
+
+        Err(Sx1280Error::WrongModem(modem))
+    }
+
+    pub fn set_data_shaping(&mut self, shaping: Shaping) -> Result<()> {
+        // check active modem
+        let modem = self.get_packet_type()?;
+        if modem == PACKET_TYPE_FLRC {
+            return self.update_flrc(FlrcConfig { shaping, ..self.flrc });
+        }
+        if modem == PACKET_TYPE_GFSK {
+            return self.update_gfsk(GfskConfig { shaping, ..self.gfsk });
+        }
+
+        // BLE shaping is fixed by the standard
+        Err(Sx1280Error::WrongModem(modem))
+    }
+
+    pub fn set_sync_word(&mut self, sync_word: &[u8]) -> Result<()> {
+        // check active modem
+        let modem = self.get_packet_type()?;
+        let len = sync_word.len();
+        if modem == PACKET_TYPE_FLRC {
+            // FLRC requires 32-bit sync word, or none at all
+            let mut flrc = self.flrc;
+            match <[u8; 4]>::try_from(sync_word) {
+                Ok(word) => {
+                    flrc.sync_words[0] = u32::from_be_bytes(word);
+                    flrc.sync_match = SyncWordMatch::Sync1;
+                }
+                Err(_) if len == 0 => flrc.sync_match = SyncWordMatch::Off,
+                Err(_) => return Err(Sx1280Error::InvalidSyncWord(len)),
+            }
+            return self.update_flrc(flrc);
+        }
+        if modem != PACKET_TYPE_GFSK {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
 
-  if modem == PACKET_TYPE_GFSK {
-    // GFSK can use up to 5 bytes as sync word
-    if len > 5 {
-      return ERR_INVALID_SYNC_WORD;
+        // GFSK can use 1 to 5 bytes as sync word
+        let mut gfsk = self.gfsk;
+        if len == 0 {
+            gfsk.sync_match = SyncWordMatch::Off;
+        } else if len <= 5 {
+            gfsk.sync_words[0] = sync_word.iter().fold(0, |word, byte| (word << 8) | *byte as u64);
+            gfsk.sync_word_length = len as u8;
+            gfsk.sync_match = SyncWordMatch::Sync1;
+        } else {
+            return Err(Sx1280Error::InvalidSyncWord(len));
+        }
+        self.update_gfsk(gfsk)
     }
 
-    // calculate sync word length parameter value
-    if len > 0 {
-      self::sync_word_len = (len - 1)*2;
+    /// Selects which of the configured sync words a GFSK or FLRC receiver accepts.
+    pub fn set_sync_word_match(&mut self, sync_match: SyncWordMatch) -> Result<()> {
+        let modem = self.get_packet_type()?;
+        if modem == PACKET_TYPE_FLRC {
+            return self.update_flrc(FlrcConfig { sync_match, ..self.flrc });
+        }
+        self.update_gfsk(GfskConfig { sync_match, ..self.gfsk })
     }
 
-  } else {
-    // FLRC requires 32-bit sync word
-    if !((len == 0) || (len == 4)) {
-      return ERR_INVALID_SYNC_WORD;
+    pub fn set_lora_sync_word(&mut self, sync_word: u8, control_bits: u8) -> Result<()> {
+        // check active modem
+        let modem = self.get_packet_type()?;
+        if modem != PACKET_TYPE_LORA {
+            return Err(Sx1280Error::WrongModem(modem));
+        }
+
+        // update register
+        let data = [(sync_word & 0xF0) | ((control_bits & 0xF0) >> 4), ((sync_word & 0x0F) << 4) | (control_bits & 0x0F)];
+        self.write_register(Register::LoRaSyncWord.address(), &data)
     }
 
-    // save sync word length parameter value
-    self::sync_word_len = len;
-  }
+    pub fn set_crc(&mut self, len: u8, initial: u32, polynomial: u16) -> Result<()> {
+        // check active modem
+        let modem = self.get_packet_type()?;
+
+        if modem == PACKET_TYPE_GFSK {
+            // update packet parameters, seed and polynomial
+            let crc = match len {
+                0 => GfskCrc::Off,
+                1 => GfskCrc::Bytes1,
+                2 => GfskCrc::Bytes2,
+                _ => return Err(Sx1280Error::InvalidCrcConfiguration(len)),
+            };
+            let crc_seed = (initial & 0xFFFF) as u16;
+            return self.update_gfsk(GfskConfig { crc, crc_seed, crc_polynomial: polynomial, ..self.gfsk });
+
+        } else if modem == PACKET_TYPE_FLRC {
+            // update packet parameters
+            let crc = match len {
+                0 => FlrcCrc::Off,
+                2 => FlrcCrc::Bytes2,
+                3 => FlrcCrc::Bytes3,
+                4 => FlrcCrc::Bytes4,
+                _ => return Err(Sx1280Error::InvalidCrcConfiguration(len)),
+            };
+            self.update_flrc(FlrcConfig { crc, ..self.flrc })?;
+
+            // set initial CRC value
+            self.write_register_value(Register::CrcInitialValue, (initial & 0xFFFF) as u64)?;
 
-  // reverse sync word byte order
-  let sync_word_buff: [u8; 5] = [0x00, 0x00, 0x00, 0x00, 0x00];
-  for i: u8 in 0...len {
-    sync_word_buff[4 - i] = sync_word[i];
-  }
+            // set CRC polynomial
+            return self.write_register_value(Register::CrcPolynomialDefinition, polynomial as u64);
+
+        } else if modem == PACKET_TYPE_BLE {
+            // update packet parameters and initial CRC value
+            let crc = match len {
+                0 => false,
+                3 => true,
+                _ => return Err(Sx1280Error::InvalidCrcConfiguration(len)),
+            };
+            return self.update_ble(BleConfig { crc, crc_init: initial & 0xFF_FFFF, ..self.ble });
+
+        } else if (modem == PACKET_TYPE_LORA) || (modem == PACKET_TYPE_RANGING) {
+            // update packet parameters
+            let crc = match len {
+                0 => false,
+                2 => true,
+                _ => return Err(Sx1280Error::InvalidCrcConfiguration(len)),
+            };
+            return self.update_lora(LoRaConfig { crc, ..self.lora }, true);
+        }
+
+        Err(Sx1280Error::WrongModem(modem))
+    }
+
+    pub fn set_whitening(&mut self, enabled: bool) -> Result<()> {
+        // check active modem
+        let modem = self.get_packet_type()?;
+        if modem == PACKET_TYPE_GFSK {
+            return self.update_gfsk(GfskConfig { whitening: enabled, ..self.gfsk });
+        }
+        self.update_ble(BleConfig { whitening: enabled, ..self.ble })
+    }
+
+    pub fn set_access_address(&mut self, access_address: u32) -> Result<()> {
+        self.update_ble(BleConfig { access_address, ..self.ble })
+    }
+
+    pub fn set_high_sensitivity_mode(&mut self, enable: bool) -> Result<()> {
+        self.write_field(Field::GAIN_MODE, if enable { 0b11 } else { 0b00 })
+    }
+
+    pub fn set_gain_control(&mut self, gain: u8) -> Result<()> {
+        if gain > 0 && gain < 14 {
+            // manual gain, with the LNA gain step (1-13) set explicitly
+            self.write_field(Field::AUTOMATIC_GAIN_CONTROL, 0)?;
+            self.write_field(Field::LNA_GAIN, gain)?;
+            self.write_field(Field::MANUAL_GAIN_ENABLE, 1)
+        } else {
+            // automatic gain if 0 or out of range
+            self.write_field(Field::AUTOMATIC_GAIN_CONTROL, 1)?;
+            self.write_field(Field::LNA_GAIN, 0x0A)?;
+            self.write_field(Field::MANUAL_GAIN_ENABLE, 0)
+        }
+    }
+}
+
+// Encodes a SetTx/SetRx timeout as [period base, count MSB, count LSB], using the finest step
+// that can still represent it
+fn encode_timeout(timeout: Duration) -> Result<[u8; 3]> {
+    if timeout.is_zero() {
+        return Ok([0x00, 0x00, 0x00]);
+    }
+    for (base, step) in PERIOD_BASE_STEPS.iter().enumerate() {
+        let count = timeout.as_nanos().div_ceil(step.as_nanos());
+        // a count of 0xFFFF means continuous mode rather than a timeout
+        if count < 0xFFFF {
+            return Ok([base as u8, (count >> 8) as u8, count as u8]);
+        }
+    }
+    Err(Sx1280Error::InvalidTimeout(timeout))
+}
 
-  // update sync word
-  let state: i16 = WriteRegister(SX128X_REG_SYNC_WORD_1_BYTE_4, sync_word_buff, 5);
-  Assert(state);
+// Encodes the SetRxDutyCycle periods as [period base, rx count MSB, rx count LSB, sleep count MSB,
+// sleep count LSB]. Both counts share one period base, so the finest step that fits the longer of
+// the two is used.
+fn encode_duty_cycle(rx: Duration, sleep: Duration) -> Result<[u8; 5]> {
+    if rx.is_zero() {
+        return Err(Sx1280Error::InvalidTimeout(rx));
+    }
+    for (base, step) in PERIOD_BASE_STEPS.iter().enumerate() {
+        let rx_count = rx.as_nanos().div_ceil(step.as_nanos());
+        let sleep_count = sleep.as_nanos().div_ceil(step.as_nanos());
+        if rx_count <= 0xFFFF && sleep_count <= 0xFFFF {
+            return Ok([base as u8, (rx_count >> 8) as u8, rx_count as u8, (sleep_count >> 8) as u8, sleep_count as u8]);
+        }
+    }
+    Err(Sx1280Error::InvalidTimeout(rx.max(sleep)))
+}
 
-  // update packet parameters
-  if self::sync_word_len == 0 {
-    self::sync_word_match = SX128X_GFSK_FLRC_SYNC_WORD_OFF;
-  } else {
-    /// \todo add support for multiple sync words
-    self::sync_word_match = SX128X_GFSK_FLRC_SYNC_WORD_1;
-  }
-  return SetPacketParamsGFSK(self::preamble_length_gfsk, self::sync_word_len, self::sync_word_match, self::header_type, self::payload_length, self::crc_gfsk, self::whitening);
+// A response of response_length bytes at the start of the buffer must leave room for a received
+// packet of up to max_rx_length bytes behind it
+fn check_response_space(response_length: usize, max_rx_length: usize) -> Result<()> {
+    if response_length + max_rx_length > SX1280_BUFFER_SIZE {
+        return Err(Sx1280Error::InvalidPayloadLength(response_length));
+    }
+    Ok(())
 }
 
-fn SetSyncWord(uint8_t syncWord, uint8_t controlBits) -> i16 {
-  // check active modem
-  if GetPacketType() != PACKET_TYPE_LORA {
-    return ERR_WRONG_MODEM;
-  }
+// Uniformly distributed time between min and max. Every RandomState is seeded differently, which
+// is plenty to keep two radios from backing off in lockstep.
+fn random_backoff(min: Duration, max: Duration) -> Duration {
+    let span = (max - min).as_micros() as u64;
+    let random = RandomState::new().build_hasher().finish();
+    min + Duration::from_micros(random % (span + 1))
+}
 
-  // update register
-  let data: [u8; 2] = [((sync_word & 0xF0) | ((control_bits & 0xF0) >> 4)) as u8, (((sync_word & 0x0F) << 4) | (control_bits & 0x0F)) as u8];
-  return WriteRegister(SX128X_REG_LORA_SYNC_WORD_MSB, data, 2);
+// A continuous wave or preamble test transmission, see SX1280::continuous_wave
+pub struct TestTx<'a> {
+    radio: &'a mut SX1280,
 }
 
-fn SetCRC(len: u8, initial: u32, polynomial: u16) -> i16 {
-  // check active modem
-  let modem: u8 = GetPacketType();
-
-  let state: i16;
-  if (modem == PACKET_TYPE_GFSK) || (modem == PACKET_TYPE_FLRC) {
-    // update packet parameters
-    if modem == PACKET_TYPE_GFSK {
-      if len > 2 {
-        return ERR_INVALID_CRC_CONFIGURATION;
-      }
-    } else {
-      if len > 3 {
-        return ERR_INVALID_CRC_CONFIGURATION;
-      }
-    }
-    self::crc_gfsk = len << 4;
-    state = SetPacketParamsGFSK(self::preamble_length_gfsk, self::sync_word_len, self::sync_word_match, self::crc_gfsk, self::whitening);
-    Assert(state);
-
-    // set initial CRC value
-    let data: [u8; 2] = [((initial >> 8) & 0xFF) as u8, (initial & 0xFF) as u8];
-    state = WriteRegister(SX128X_REG_CRC_INITIAL_MSB, data, 2);
-    Assert(state);
-
-    // set CRC polynomial
-    data[0] = ((polynomial >> 8) & 0xFF) as u8;
-    data[1] = (polynomial & 0xFF) as u8;
-    state = WriteRegister(SX128X_REG_CRC_POLYNOMIAL_MSB, data, 2);
-    return state;
-
-  } else if modem == PACKET_TYPE_BLE {
-    // update packet parameters
-    if len == 0 {
-      self::crc_ble = SX128X_BLE_CRC_OFF;
-    } else if len == 3 {
-      self::crc_ble = SX128X_BLE_CRC_3_BYTE;
-    } else {
-      return ERR_INVALID_CRC_CONFIGURATION;
-    }
-    state = SetPacketParamsBLE(self::connection_state, self::crc_ble, self::ble_test_payload, self::whitening);
-    Assert(state);
-
-    // set initial CRC value
-    let data: [u8, 3] = [((initial >> 16) & 0xFF) as u8, ((initial >> 8) & 0xFF) as u8, (initial & 0xFF) as u8];
-    state = WriteRegister(SX128X_REG_BLE_CRC_INITIAL_MSB, data, 3);
-    return state;
-
-  } else if (modem == PACKET_TYPE_LORA) || (modem == PACKET_TYPE_RANGING) {
-    // update packet parameters
-    if len == 0 {
-      self::crc_lora = SX128X_LORA_CRC_OFF;
-    } else if(len == 2) {
-      self::crc_lora = SX128X_LORA_CRC_ON;
-    } else {
-      return ERR_INVALID_CRC_CONFIGURATION;
-    }
-    state = SetPacketParamsLoRa(self::preamble_length_lora, self::header_type, self::payload_length, self::crc_lora, self::invert_iq_enabled);
-    return state;
-  }
-
-  return ERR_UNKNOWN;
+impl Drop for TestTx<'_> {
+    fn drop(&mut self) {
+        let _ = self.radio.set_standby(StandbyMode::Rc);
+    }
 }
 
-fn SetWhitening(enabled: bool) -> i16 {
-  // check active modem
-  let modem: u8 = GetPacketType();
-  if !((modem == PACKET_TYPE_GFSK) || (modem == PACKET_TYPE_BLE)) {
-    return ERR_WRONG_MODEM;
-  }
-
-  // update packet parameters
-  if enabled {
-    self::whitening = SX128X_GFSK_BLE_WHITENING_ON;
-  } else {
-    self::whitening = SX128X_GFSK_BLE_WHITENING_OFF;
-  }
-
-  if modem == PACKET_TYPE_GFSK {
-    return SetPacketParamsGFSK(self::preamble_length_gfsk, self::sync_word_len, self::sync_word_match, self::crc_gfsk, self::whitening);
-  }
-  return SetPacketParamsBLE(self::connection_state, self::crc_ble, self::ble_test_payload, self::whitening);
+// Packets picked up in continuous receive mode, see SX1280::receive_continuous
+pub struct ContinuousRx<'a> {
+    radio: &'a mut SX1280,
 }
 
-fn SetAccessAddress(addr: u32) -> i16 {
-  // check active modem
-  if GetPacketType() != PACKET_TYPE_BLE {
-    return ERR_WRONG_MODEM;
-  }
+impl Iterator for ContinuousRx<'_> {
+    type Item = Result<RxPacket>;
 
-  // set the address
-  let addrBuff: [u8, 4] = [((addr >> 24) & 0xFF) as u8, ((addr >> 16) & 0xFF) as u8, ((addr >> 8) & 0xFF) as u8, (addr & 0xFF) as u8];
-  return WriteRegister(SX128X_REG_ACCESS_ADDRESS_BYTE_3, addrBuff, 4);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let packet = self.radio.poll_irq(RX_EVENTS, Duration::ZERO).and_then(|irq| {
+                self.radio.clear_irq_status(Irq::ALL)?;
+                self.radio.read_packet(irq)
+            });
+            match packet {
+                Ok(Some(packet)) => return Some(Ok(packet)),
+                // the radio keeps listening after a timeout in continuous mode
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
 }
 
-fn SetHighSensitivityMode(enable: bool) -> i16 {
-  // read the current registers
-  let rx_gain: u8 = 0;
-  let state: i16 = ReadRegister(SX128X_REG_GAIN_MODE, &rx_gain, 1);
-  Assert(state);
-
-  if enable {
-    rx_gain |= 0xC0; // Set bits 6 and 7
-  } else {
-    rx_gain &= ~0xC0; // Unset bits 6 and 7
-  }
-
-  // update all values
-  state = WriteRegister(SX128X_REG_GAIN_MODE, &rx_gain, 1);
-  return state;
+impl Drop for ContinuousRx<'_> {
+    fn drop(&mut self) {
+        let _ = self.radio.set_standby(StandbyMode::Rc);
+    }
 }
 
-fn SetGainControl(gain: u8) -> i16 {
-  // read the current registers
-  let manual_gain_setting: u8 = 0;
-  let state: i16 = ReadRegister(SX128X_REG_MANUAL_GAIN_CONTROL_ENABLE_2, &manual_gain_setting, 1);
-  Assert(state);
-  let lna_gain_value: u8 = 0;
-  state = ReadRegister(SX128X_REG_MANUAL_GAIN_SETTING, &lna_gain_value, 1);
-  Assert(state);
-  let lna_gain_control: u8 = 0;
-  state = ReadRegister(SX128X_REG_MANUAL_GAIN_CONTROL_ENABLE_1, &lna_gain_control, 1);
-  Assert(state);
-
-  // set the gain
-  if gain > 0 && gain < 14 {
-    // Set manual gain
-    manual_gain_setting &= ~0x01; // Set bit 0 to 0 (Enable Manual Gain Control)
-    lna_gain_value &= 0xF0; // Bits 0, 1, 2 and 3 to 0
-    lna_gain_value |= gain; // Set bits 0, 1, 2 and 3 to Manual Gain Setting (1-13)
-    lna_gain_control |= 0x80; // Set bit 7 to 1 (Enable Manual Gain Control)
-  } else {
-    // Set automatic gain if 0 or out of range
-    manual_gain_setting |= 0x01; // Set bit 0 to 1 (Enable Automatic Gain Control)
-    lna_gain_value &= 0xF0; // Bits 0, 1, 2 and 3 to 0
-    lna_gain_value |= 0x0A; // Set bits 0, 1, 2 and 3 to Manual Gain Setting (1-13)
-    lna_gain_control &= ~0x80; // Set bit 7 to 0 (Enable Automatic Gain Control)
-  }
-
-  // update all values
-  state = WriteRegister(SX128X_REG_MANUAL_GAIN_CONTROL_ENABLE_2, &manual_gain_setting, 1);
-  Assert(state);
-  state = WriteRegister(SX128X_REG_MANUAL_GAIN_SETTING, &lna_gain_value, 1);
-  Assert(state);
-  state = WriteRegister(SX128X_REG_MANUAL_GAIN_CONTROL_ENABLE_1, &lna_gain_control, 1);
-  return state;
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nanos(nanos: u64) -> Duration {
+        Duration::from_nanos(nanos)
+    }
+
+    #[test]
+    fn encode_timeout_single_shot() {
+        assert_eq!(encode_timeout(Duration::ZERO).unwrap(), [0x00, 0x00, 0x00]);
+        assert_eq!(encode_timeout(nanos(1)).unwrap(), [0x00, 0x00, 0x01]);
+        assert_eq!(encode_timeout(nanos(15_625)).unwrap(), [0x00, 0x00, 0x01]);
+        assert_eq!(encode_timeout(nanos(15_626)).unwrap(), [0x00, 0x00, 0x02]);
+    }
+
+    #[test]
+    fn encode_timeout_period_base_boundaries() {
+        // 0xFFFE steps is the longest count on each base, 0xFFFF would mean continuous
+        assert_eq!(encode_timeout(nanos(1_023_968_750)).unwrap(), [0x00, 0xFF, 0xFE]);
+        assert_eq!(encode_timeout(nanos(1_023_968_751)).unwrap(), [0x01, 0x40, 0x00]);
+        assert_eq!(encode_timeout(nanos(4_095_875_000)).unwrap(), [0x01, 0xFF, 0xFE]);
+        assert_eq!(encode_timeout(nanos(4_095_875_001)).unwrap(), [0x02, 0x10, 0x00]);
+        assert_eq!(encode_timeout(nanos(65_534_000_000)).unwrap(), [0x02, 0xFF, 0xFE]);
+        assert_eq!(encode_timeout(nanos(65_534_000_001)).unwrap(), [0x03, 0x40, 0x00]);
+        assert_eq!(encode_timeout(nanos(262_136_000_000)).unwrap(), [0x03, 0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn encode_timeout_out_of_range() {
+        assert!(encode_timeout(nanos(262_136_000_001)).is_err());
+        assert!(encode_timeout(Duration::from_secs(3600)).is_err());
+    }
+
+    #[test]
+    fn encode_duty_cycle_shares_period_base() {
+        assert_eq!(encode_duty_cycle(nanos(15_625), Duration::ZERO).unwrap(), [0x00, 0x00, 0x01, 0x00, 0x00]);
+        // 0xFFFF steps is allowed here, and the longer period decides the base for both
+        assert_eq!(
+            encode_duty_cycle(Duration::from_millis(1), nanos(1_023_984_375)).unwrap(),
+            [0x00, 0x00, 0x40, 0xFF, 0xFF]
+        );
+        assert_eq!(
+            encode_duty_cycle(Duration::from_millis(1), nanos(1_023_984_376)).unwrap(),
+            [0x01, 0x00, 0x10, 0x40, 0x00]
+        );
+        assert_eq!(
+            encode_duty_cycle(nanos(4_095_937_501), Duration::from_millis(1)).unwrap(),
+            [0x02, 0x10, 0x00, 0x00, 0x01]
+        );
+        assert_eq!(
+            encode_duty_cycle(Duration::from_millis(262_140), Duration::ZERO).unwrap(),
+            [0x03, 0xFF, 0xFF, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn response_space_lora_explicit() {
+        let lora = LoRaConfig::default();
+        assert_eq!(lora.header, LoRaHeader::Explicit);
+        for length in [2, 16, 128] {
+            assert!(check_response_space(length, lora.max_rx_length(length)).is_ok(), "{} bytes", length);
+        }
+        assert!(check_response_space(129, lora.max_rx_length(129)).is_err());
+    }
+
+    #[test]
+    fn response_space_fixed_length() {
+        // implicit header packets all have the response's length
+        let lora = LoRaConfig { header: LoRaHeader::Implicit(128), ..LoRaConfig::default() };
+        assert!(check_response_space(128, lora.max_rx_length(128)).is_ok());
+        let lora = LoRaConfig { header: LoRaHeader::Implicit(129), ..LoRaConfig::default() };
+        assert!(check_response_space(129, lora.max_rx_length(129)).is_err());
+        assert!(check_response_space(10, PacketLength::Fixed(246).max_rx_length(10)).is_ok());
+        assert!(check_response_space(10, PacketLength::Fixed(247).max_rx_length(10)).is_err());
+    }
+
+    #[test]
+    fn encode_duty_cycle_out_of_range() {
+        assert!(encode_duty_cycle(Duration::ZERO, Duration::from_millis(10)).is_err());
+        assert!(encode_duty_cycle(nanos(262_140_000_001), Duration::ZERO).is_err());
+        assert!(encode_duty_cycle(Duration::from_millis(1), nanos(262_140_000_001)).is_err());
+    }
+}